@@ -0,0 +1,7 @@
+use core::any::Any;
+
+/// 块设备接口：以块为单位读写，`buf`的长度必须是BLOCK_SZ
+pub trait BlockDevice: Send + Sync + Any {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]);
+    fn write_block(&self, block_id: usize, buf: &[u8]);
+}
@@ -0,0 +1,24 @@
+//! 一个简单的文件系统，与内核解耦：只依赖块设备接口，因此既能被内核使用，
+//! 也能在宿主机上用文件模拟块设备来打包镜像和测试
+
+#![no_std]
+
+extern crate alloc;
+
+mod bitmap;
+mod block_cache;
+mod block_dev;
+mod efs;
+mod layout;
+mod vfs;
+
+/// 块大小
+pub const BLOCK_SZ: usize = 512;
+
+use bitmap::Bitmap;
+use block_cache::{block_cache_sync_all, get_block_cache};
+pub use block_dev::BlockDevice;
+pub use efs::EasyFileSystem;
+pub use layout::{MAX_FILE_SIZE, NAME_LENGTH_LIMIT};
+use layout::*;
+pub use vfs::Inode;
@@ -0,0 +1,367 @@
+use super::{
+    block_cache_sync_all, get_block_cache, BlockDevice, DirEntry, DiskInode, DiskInodeType,
+    EasyFileSystem, BLOCK_SZ, DIRENT_SZ, MAX_FILE_SIZE,
+};
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use spin::{Mutex, MutexGuard};
+
+/// 暴露给文件系统使用者的索引节点，它只记录DiskInode在磁盘上的位置，
+/// 所有访问都经过块缓存
+pub struct Inode {
+    block_id: usize,
+    block_offset: usize,
+    fs: Arc<Mutex<EasyFileSystem>>,
+    block_device: Arc<dyn BlockDevice>,
+}
+
+impl Inode {
+    /// We should not acquire efs lock here.
+    pub fn new(
+        block_id: u32,
+        block_offset: usize,
+        fs: Arc<Mutex<EasyFileSystem>>,
+        block_device: Arc<dyn BlockDevice>,
+    ) -> Self {
+        Self {
+            block_id: block_id as usize,
+            block_offset,
+            fs,
+            block_device,
+        }
+    }
+    fn read_disk_inode<V>(&self, f: impl FnOnce(&DiskInode) -> V) -> V {
+        get_block_cache(self.block_id, Arc::clone(&self.block_device))
+            .lock()
+            .read(self.block_offset, f)
+    }
+    fn modify_disk_inode<V>(&self, f: impl FnOnce(&mut DiskInode) -> V) -> V {
+        get_block_cache(self.block_id, Arc::clone(&self.block_device))
+            .lock()
+            .modify(self.block_offset, f)
+    }
+    /// 修改编号为`inode_id`的索引节点，不需要为它构造Inode
+    fn modify_other_disk_inode<V>(
+        &self,
+        inode_id: u32,
+        fs: &MutexGuard<EasyFileSystem>,
+        f: impl FnOnce(&mut DiskInode) -> V,
+    ) -> V {
+        let (block_id, block_offset) = fs.get_disk_inode_pos(inode_id);
+        get_block_cache(block_id as usize, Arc::clone(&self.block_device))
+            .lock()
+            .modify(block_offset, f)
+    }
+    /// 读出目录中的第`i`个目录项
+    fn dirent_at(&self, i: usize, disk_inode: &DiskInode) -> DirEntry {
+        let mut dirent = DirEntry::empty();
+        assert_eq!(
+            disk_inode.read_at(i * DIRENT_SZ, dirent.as_bytes_mut(), &self.block_device,),
+            DIRENT_SZ,
+        );
+        dirent
+    }
+    /// 在目录中按名字查找，返回目录项的序号和对应的索引节点编号
+    fn find_dirent(&self, name: &str, disk_inode: &DiskInode) -> Option<(usize, u32)> {
+        // assert it is a directory
+        assert!(disk_inode.is_dir());
+        let file_count = (disk_inode.size as usize) / DIRENT_SZ;
+        (0..file_count).find_map(|i| {
+            let dirent = self.dirent_at(i, disk_inode);
+            if !dirent.is_empty() && dirent.name() == name {
+                Some((i, dirent.inode_number()))
+            } else {
+                None
+            }
+        })
+    }
+    /// 在目录中按名字查找，返回对应的索引节点编号
+    fn find_inode_id(&self, name: &str, disk_inode: &DiskInode) -> Option<u32> {
+        self.find_dirent(name, disk_inode)
+            .map(|(_, inode_id)| inode_id)
+    }
+    /// 获取编号为`inode_id`的索引节点，正在使用中时返回同一个Inode
+    fn get_inode(&self, inode_id: u32, fs: &mut MutexGuard<EasyFileSystem>) -> Arc<Inode> {
+        if let Some(inode) = fs.inodes.get(&inode_id).and_then(|weak| weak.upgrade()) {
+            return inode;
+        }
+        let (block_id, block_offset) = fs.get_disk_inode_pos(inode_id);
+        let inode = Arc::new(Self::new(
+            block_id,
+            block_offset,
+            self.fs.clone(),
+            self.block_device.clone(),
+        ));
+        fs.inodes.insert(inode_id, Arc::downgrade(&inode));
+        inode
+    }
+    /// 在当前目录下按名字查找文件
+    pub fn find(&self, name: &str) -> Option<Arc<Inode>> {
+        let mut fs = self.fs.lock();
+        let inode_id = self.read_disk_inode(|disk_inode| self.find_inode_id(name, disk_inode))?;
+        Some(self.get_inode(inode_id, &mut fs))
+    }
+    /// 把文件扩大到`new_size`字节，需要的块从文件系统中分配，返回扩大之后的文件大小。
+    /// 磁盘空间不足时只扩大到分到的块所能容纳的大小，用不上的块还给文件系统
+    fn increase_size(
+        &self,
+        new_size: u32,
+        disk_inode: &mut DiskInode,
+        fs: &mut MutexGuard<EasyFileSystem>,
+    ) -> u32 {
+        if new_size < disk_inode.size {
+            return disk_inode.size;
+        }
+        let blocks_needed = disk_inode.blocks_num_needed(new_size);
+        let mut v: Vec<u32> = Vec::new();
+        for _ in 0..blocks_needed {
+            match fs.alloc_data() {
+                Some(block_id) => v.push(block_id),
+                None => break,
+            }
+        }
+        let mut new_size = new_size;
+        while disk_inode.blocks_num_needed(new_size) as usize > v.len() {
+            // 每次退回到上一个块的边界，但不会比原来的文件小
+            new_size = ((new_size - 1) / BLOCK_SZ as u32 * BLOCK_SZ as u32).max(disk_inode.size);
+        }
+        for block_id in v.split_off(disk_inode.blocks_num_needed(new_size) as usize) {
+            fs.dealloc_data(block_id);
+        }
+        disk_inode.increase_size(new_size, v, &self.block_device);
+        new_size
+    }
+    /// 在目录中加入一个目录项，优先复用被删除的目录项留下的位置。
+    /// 磁盘已满、目录无法扩大时返回false
+    fn add_dirent(
+        &self,
+        name: &str,
+        inode_id: u32,
+        disk_inode: &mut DiskInode,
+        fs: &mut MutexGuard<EasyFileSystem>,
+    ) -> bool {
+        let file_count = (disk_inode.size as usize) / DIRENT_SZ;
+        let slot = (0..file_count)
+            .find(|&i| self.dirent_at(i, disk_inode).is_empty())
+            .unwrap_or(file_count);
+        if slot == file_count {
+            let new_size = ((file_count + 1) * DIRENT_SZ) as u32;
+            if self.increase_size(new_size, disk_inode, fs) < new_size {
+                return false;
+            }
+        }
+        let dirent = DirEntry::new(name, inode_id);
+        disk_inode.write_at(slot * DIRENT_SZ, dirent.as_bytes(), &self.block_device);
+        true
+    }
+    /// 在当前目录下新建一个文件或目录，同名的目录项已存在或者磁盘已满时返回None
+    fn create_inode(&self, name: &str, type_: DiskInodeType) -> Option<Arc<Inode>> {
+        let mut fs = self.fs.lock();
+        if self
+            .read_disk_inode(|dir_inode| self.find_inode_id(name, dir_inode))
+            .is_some()
+        {
+            return None;
+        }
+        let is_dir = type_ == DiskInodeType::Directory;
+        let new_inode_id = fs.alloc_inode()?;
+        let parent_inode_id = fs.get_inode_id(self.block_id as u32, self.block_offset);
+        let (new_inode_block_id, new_inode_block_offset) = fs.get_disk_inode_pos(new_inode_id);
+        let created = get_block_cache(new_inode_block_id as usize, Arc::clone(&self.block_device))
+            .lock()
+            .modify(new_inode_block_offset, |new_inode: &mut DiskInode| {
+                new_inode.initialize(type_);
+                // 新目录的"."指向自己，".."指向父目录
+                !is_dir
+                    || (self.add_dirent(".", new_inode_id, new_inode, &mut fs)
+                        && self.add_dirent("..", parent_inode_id, new_inode, &mut fs))
+            })
+            && self.modify_disk_inode(|dir_inode| {
+                if !self.add_dirent(name, new_inode_id, dir_inode, &mut fs) {
+                    return false;
+                }
+                if is_dir {
+                    // 子目录的".."
+                    dir_inode.nlink += 1;
+                }
+                true
+            });
+        if !created {
+            // 磁盘已满，回收新索引节点和它已经分到的数据块
+            self.modify_other_disk_inode(new_inode_id, &fs, |new_inode| new_inode.nlink = 0);
+            fs.free_inode(new_inode_id);
+            return None;
+        }
+        block_cache_sync_all();
+        Some(self.get_inode(new_inode_id, &mut fs))
+        // release efs lock automatically by compiler
+    }
+    /// 在当前目录下创建一个普通文件，同名文件已存在时返回None
+    pub fn create(&self, name: &str) -> Option<Arc<Inode>> {
+        self.create_inode(name, DiskInodeType::File)
+    }
+    /// 在当前目录下创建一个子目录，同名文件已存在时返回None
+    pub fn create_dir(&self, name: &str) -> Option<Arc<Inode>> {
+        self.create_inode(name, DiskInodeType::Directory)
+    }
+    /// 在当前目录下创建一个指向`target`的硬链接。
+    /// 同名文件已存在、`target`是目录或者磁盘已满时返回false
+    pub fn link(&self, name: &str, target: &Inode) -> bool {
+        let mut fs = self.fs.lock();
+        if target.read_disk_inode(|disk_inode| disk_inode.is_dir())
+            || self
+                .read_disk_inode(|dir_inode| self.find_inode_id(name, dir_inode))
+                .is_some()
+        {
+            return false;
+        }
+        let target_id = fs.get_inode_id(target.block_id as u32, target.block_offset);
+        if !self.modify_disk_inode(|dir_inode| self.add_dirent(name, target_id, dir_inode, &mut fs))
+        {
+            return false;
+        }
+        target.modify_disk_inode(|disk_inode| disk_inode.nlink += 1);
+        block_cache_sync_all();
+        true
+    }
+    /// 删除当前目录下名为`name`的目录项，不存在时返回false。
+    /// 删除目录前调用者需要保证它是空的。
+    /// 链接数减为0的文件如果没有被打开，立即回收；否则等到最后一个使用者离开时回收
+    pub fn unlink(&self, name: &str) -> bool {
+        let mut fs = self.fs.lock();
+        let (slot, inode_id) =
+            match self.read_disk_inode(|dir_inode| self.find_dirent(name, dir_inode)) {
+                Some(found) => found,
+                None => return false,
+            };
+        let (nlink, is_dir) = self.modify_other_disk_inode(inode_id, &fs, |disk_inode| {
+            if disk_inode.is_dir() {
+                // 父目录中的目录项和它自己的"."
+                disk_inode.nlink -= 2;
+            } else {
+                disk_inode.nlink -= 1;
+            }
+            (disk_inode.nlink, disk_inode.is_dir())
+        });
+        self.modify_disk_inode(|dir_inode| {
+            dir_inode.write_at(
+                slot * DIRENT_SZ,
+                DirEntry::empty().as_bytes(),
+                &self.block_device,
+            );
+            if is_dir {
+                // 子目录的".."
+                dir_inode.nlink -= 1;
+            }
+        });
+        let in_use = matches!(fs.inodes.get(&inode_id), Some(weak) if weak.strong_count() > 0);
+        if nlink == 0 && !in_use {
+            fs.free_inode(inode_id);
+        }
+        block_cache_sync_all();
+        true
+    }
+    /// 目录中是否只有"."和".."
+    pub fn is_empty_dir(&self) -> bool {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| {
+            let file_count = (disk_inode.size as usize) / DIRENT_SZ;
+            (0..file_count).all(|i| {
+                let dirent = self.dirent_at(i, disk_inode);
+                dirent.is_empty() || dirent.name() == "." || dirent.name() == ".."
+            })
+        })
+    }
+    /// 索引节点编号
+    pub fn inode_id(&self) -> u32 {
+        self.fs
+            .lock()
+            .get_inode_id(self.block_id as u32, self.block_offset)
+    }
+    /// 文件大小（字节）
+    pub fn size(&self) -> usize {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| disk_inode.size as usize)
+    }
+    pub fn is_dir(&self) -> bool {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| disk_inode.is_dir())
+    }
+    /// 硬链接数
+    pub fn nlink(&self) -> u32 {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| disk_inode.nlink)
+    }
+    /// 列出当前目录下的所有文件名，不包括"."和".."
+    pub fn ls(&self) -> Vec<String> {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| {
+            let file_count = (disk_inode.size as usize) / DIRENT_SZ;
+            let mut v: Vec<String> = Vec::new();
+            for i in 0..file_count {
+                let dirent = self.dirent_at(i, disk_inode);
+                if dirent.is_empty() || dirent.name() == "." || dirent.name() == ".." {
+                    continue;
+                }
+                v.push(String::from(dirent.name()));
+            }
+            v
+        })
+    }
+    /// 从文件的`offset`处读数据
+    pub fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| disk_inode.read_at(offset, buf, &self.block_device))
+    }
+    /// 向文件的`offset`处写数据，必要时扩大文件，返回实际写入的字节数。
+    /// 超出MAX_FILE_SIZE的部分不会写入，磁盘空间不足时也只写入能放下的部分
+    pub fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
+        let end = offset.saturating_add(buf.len()).min(MAX_FILE_SIZE);
+        if offset >= end {
+            return 0;
+        }
+        let mut fs = self.fs.lock();
+        let size = self.modify_disk_inode(|disk_inode| {
+            let new_size = self.increase_size(end as u32, disk_inode, &mut fs) as usize;
+            if offset >= new_size {
+                return 0;
+            }
+            disk_inode.write_at(offset, &buf[..end - offset], &self.block_device)
+        });
+        block_cache_sync_all();
+        size
+    }
+    /// 清空文件内容，回收所有数据块
+    pub fn clear(&self) {
+        let mut fs = self.fs.lock();
+        self.modify_disk_inode(|disk_inode| {
+            let size = disk_inode.size;
+            let data_blocks_dealloc = disk_inode.clear_size(&self.block_device);
+            assert!(data_blocks_dealloc.len() == DiskInode::total_blocks(size) as usize);
+            for data_block in data_blocks_dealloc.into_iter() {
+                fs.dealloc_data(data_block);
+            }
+        });
+        block_cache_sync_all();
+    }
+}
+
+impl Drop for Inode {
+    /// 最后一个使用者离开时，如果文件已经没有任何链接，就回收它占用的空间
+    fn drop(&mut self) {
+        let mut fs = self.fs.lock();
+        let inode_id = fs.get_inode_id(self.block_id as u32, self.block_offset);
+        match fs.inodes.get(&inode_id) {
+            // 同一个索引节点还有其他的Inode在使用，例如不在缓存中的根目录
+            Some(weak) if weak.strong_count() > 0 => return,
+            Some(_) => {
+                fs.inodes.remove(&inode_id);
+            }
+            None => {}
+        }
+        if self.read_disk_inode(|disk_inode| disk_inode.nlink) == 0 {
+            fs.free_inode(inode_id);
+        }
+    }
+}
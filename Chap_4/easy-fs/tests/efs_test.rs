@@ -0,0 +1,226 @@
+use easy_fs::{BlockDevice, EasyFileSystem, BLOCK_SZ, MAX_FILE_SIZE};
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::sync::{Arc, Mutex};
+
+/// 用宿主机上的文件模拟块设备
+struct BlockFile(Mutex<File>);
+
+impl BlockDevice for BlockFile {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) {
+        let mut file = self.0.lock().unwrap();
+        file.seek(SeekFrom::Start((block_id * BLOCK_SZ) as u64))
+            .expect("Error when seeking!");
+        assert_eq!(file.read(buf).unwrap(), BLOCK_SZ, "Not a complete block!");
+    }
+
+    fn write_block(&self, block_id: usize, buf: &[u8]) {
+        let mut file = self.0.lock().unwrap();
+        file.seek(SeekFrom::Start((block_id * BLOCK_SZ) as u64))
+            .expect("Error when seeking!");
+        assert_eq!(file.write(buf).unwrap(), BLOCK_SZ, "Not a complete block!");
+    }
+}
+
+const TOTAL_BLOCKS: u32 = 4096;
+
+fn block_file(name: &str) -> Arc<dyn BlockDevice> {
+    let path = std::env::temp_dir().join(name);
+    let f = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(path)
+        .unwrap();
+    f.set_len(TOTAL_BLOCKS as u64 * BLOCK_SZ as u64).unwrap();
+    Arc::new(BlockFile(Mutex::new(f)))
+}
+
+#[test]
+fn efs_test() {
+    let block_file = block_file("easy-fs-efs-test.img");
+    EasyFileSystem::create(block_file.clone(), TOTAL_BLOCKS, 1);
+    let efs = EasyFileSystem::open(block_file.clone());
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    root_inode.create("filea");
+    root_inode.create("fileb");
+    assert!(root_inode.create("filea").is_none());
+    assert_eq!(root_inode.ls(), vec!["filea", "fileb"]);
+
+    let filea = root_inode.find("filea").unwrap();
+    let greet_str = "Hello, world!";
+    filea.write_at(0, greet_str.as_bytes());
+    let mut buffer = [0u8; 233];
+    let len = filea.read_at(0, &mut buffer);
+    assert_eq!(greet_str, core::str::from_utf8(&buffer[..len]).unwrap());
+    assert!(root_inode.find("filec").is_none());
+
+    // 索引节点编号、大小、类型和链接数
+    assert_eq!(root_inode.inode_id(), 0);
+    assert!(root_inode.is_dir());
+    assert_eq!(filea.inode_id(), 1);
+    assert_eq!(root_inode.find("fileb").unwrap().inode_id(), 2);
+    assert!(!filea.is_dir());
+    assert_eq!(filea.size(), greet_str.len());
+    assert_eq!(filea.nlink(), 1);
+
+    // 依次覆盖直接索引、一级间接索引和二级间接索引，并检查读回的内容
+    let mut random_str_test = |len: usize| {
+        filea.clear();
+        assert_eq!(filea.read_at(0, &mut buffer), 0);
+        let mut s = String::new();
+        for i in 0..len {
+            s.push(char::from(b'0' + (i * 7 % 10) as u8));
+        }
+        filea.write_at(0, s.as_bytes());
+        let mut read_buffer = [0u8; 127];
+        let mut offset = 0usize;
+        let mut read_str = String::new();
+        loop {
+            let len = filea.read_at(offset, &mut read_buffer);
+            if len == 0 {
+                break;
+            }
+            offset += len;
+            read_str.push_str(core::str::from_utf8(&read_buffer[..len]).unwrap());
+        }
+        assert_eq!(s, read_str);
+    };
+
+    random_str_test(4 * BLOCK_SZ);
+    random_str_test(8 * BLOCK_SZ + BLOCK_SZ / 2);
+    random_str_test(100 * BLOCK_SZ);
+    random_str_test(70 * BLOCK_SZ + BLOCK_SZ / 7);
+    random_str_test((12 + 128) * BLOCK_SZ);
+    random_str_test(400 * BLOCK_SZ);
+    random_str_test(1000 * BLOCK_SZ);
+    random_str_test(2000 * BLOCK_SZ);
+}
+
+#[test]
+fn efs_reopen_test() {
+    let block_file = block_file("easy-fs-reopen-test.img");
+    let efs = EasyFileSystem::create(block_file.clone(), TOTAL_BLOCKS, 1);
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    let file = root_inode.create("persist").unwrap();
+    file.write_at(0, b"persistent data");
+    drop(file);
+    drop(root_inode);
+    drop(efs);
+
+    // 重新挂载之后文件和内容都应当还在
+    let efs = EasyFileSystem::open(block_file);
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    let file = root_inode.find("persist").unwrap();
+    let mut buffer = [0u8; 32];
+    let len = file.read_at(0, &mut buffer);
+    assert_eq!(&buffer[..len], b"persistent data");
+}
+
+#[test]
+fn efs_dir_link_test() {
+    let block_file = block_file("easy-fs-dir-link-test.img");
+    let efs = EasyFileSystem::create(block_file.clone(), TOTAL_BLOCKS, 1);
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    assert_eq!(root_inode.nlink(), 2);
+
+    // 目录层次："."和".."不出现在ls中，但可以被find到
+    let dir = root_inode.create_dir("dir").unwrap();
+    assert!(dir.is_dir());
+    assert_eq!(dir.nlink(), 2);
+    assert_eq!(root_inode.nlink(), 3);
+    assert_eq!(root_inode.ls(), vec!["dir"]);
+    assert!(dir.ls().is_empty());
+    assert!(dir.is_empty_dir());
+    assert_eq!(dir.find(".").unwrap().inode_id(), dir.inode_id());
+    assert_eq!(dir.find("..").unwrap().inode_id(), 0);
+    assert!(root_inode.create_dir("dir").is_none());
+
+    // 硬链接共享同一个索引节点，同一个文件只对应一个Inode
+    let file = dir.create("file").unwrap();
+    file.write_at(0, b"linked");
+    assert!(root_inode.link("alias", &file));
+    assert!(!root_inode.link("alias", &file));
+    assert!(!root_inode.link("dir2", &dir));
+    assert_eq!(file.nlink(), 2);
+    let alias = root_inode.find("alias").unwrap();
+    assert!(Arc::ptr_eq(&alias, &file));
+    drop(alias);
+    assert!(!dir.is_empty_dir());
+
+    // 删除一个链接之后另一个链接仍然可用
+    assert!(dir.unlink("file"));
+    assert!(!dir.unlink("file"));
+    assert_eq!(file.nlink(), 1);
+    let mut buffer = [0u8; 16];
+    let len = root_inode.find("alias").unwrap().read_at(0, &mut buffer);
+    assert_eq!(&buffer[..len], b"linked");
+
+    // 最后一个链接被删除后，文件在关闭之前仍然可以读写，关闭之后才被回收
+    let file_id = file.inode_id();
+    assert!(root_inode.unlink("alias"));
+    assert_eq!(file.nlink(), 0);
+    assert!(root_inode.find("alias").is_none());
+    file.write_at(6, b"!");
+    let len = file.read_at(0, &mut buffer);
+    assert_eq!(&buffer[..len], b"linked!");
+    drop(file);
+    assert_eq!(root_inode.create("reuse").unwrap().inode_id(), file_id);
+
+    // 没有被打开的文件在最后一个链接被删除时立即回收，目录项的位置也会被复用
+    let reuse_id = root_inode.find("reuse").unwrap().inode_id();
+    assert!(root_inode.unlink("reuse"));
+    assert_eq!(root_inode.create("again").unwrap().inode_id(), reuse_id);
+
+    // 删除空目录
+    assert!(dir.is_empty_dir());
+    let dir_id = dir.inode_id();
+    drop(dir);
+    assert!(root_inode.unlink("dir"));
+    assert_eq!(root_inode.nlink(), 2);
+    assert_eq!(root_inode.ls(), vec!["again"]);
+    assert_eq!(root_inode.create_dir("newdir").unwrap().inode_id(), dir_id);
+
+    // 重新挂载之后目录结构和链接数都还在
+    drop(root_inode);
+    drop(efs);
+    let efs = EasyFileSystem::open(block_file);
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    assert_eq!(root_inode.nlink(), 3);
+    let newdir = root_inode.find("newdir").unwrap();
+    assert_eq!(newdir.find("..").unwrap().inode_id(), 0);
+}
+
+#[test]
+fn efs_full_test() {
+    let block_file = block_file("easy-fs-full-test.img");
+    let efs = EasyFileSystem::create(block_file, TOTAL_BLOCKS, 1);
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    let file = root_inode.create("big").unwrap();
+
+    // 超出最大文件大小的位置写不进去，文件也不会被扩大
+    assert_eq!(file.write_at(MAX_FILE_SIZE, b"x"), 0);
+    assert_eq!(file.write_at(usize::MAX, b"x"), 0);
+    assert_eq!(file.size(), 0);
+
+    // 磁盘放不下时只写入能放下的部分，写满之后再也写不进去
+    let data = vec![0x5au8; TOTAL_BLOCKS as usize * BLOCK_SZ];
+    let written = file.write_at(0, &data);
+    assert!(written > 0 && written < data.len());
+    assert_eq!(written % BLOCK_SZ, 0);
+    assert_eq!(file.size(), written);
+    assert_eq!(file.write_at(written, b"more"), 0);
+    let mut buffer = [0u8; BLOCK_SZ];
+    assert_eq!(file.read_at(written - BLOCK_SZ, &mut buffer), BLOCK_SZ);
+    assert!(buffer.iter().all(|&byte| byte == 0x5a));
+
+    // 新目录分不到数据块，创建失败，已经分配的索引节点被回收
+    assert!(root_inode.create_dir("dir").is_none());
+    assert_eq!(root_inode.ls(), vec!["big"]);
+    assert_eq!(root_inode.create("small").unwrap().inode_id(), 2);
+
+    // 没有用上的块都还给了文件系统，清空之后还能写入同样多的数据
+    file.clear();
+    assert_eq!(file.write_at(0, &data), written);
+}
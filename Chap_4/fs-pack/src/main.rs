@@ -0,0 +1,192 @@
+//! 宿主机上的打包工具：把用户程序的ELF文件写入一个easy-fs镜像，供QEMU的virtio-blk设备使用
+use clap::{Arg, Command};
+use easy_fs::{BlockDevice, EasyFileSystem, BLOCK_SZ};
+use std::fs::{read_dir, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// 文件系统的大小：16MiB
+const TOTAL_BLOCKS: u32 = 16 * 2048;
+/// 文件系统之后留给内核的交换区：8MiB，与内核config中的SWAP_START_BLOCK和SWAP_PAGES一致
+const SWAP_BLOCKS: u32 = 8 * 2048;
+/// 索引节点位图占用的块数，最多可以有4096个文件
+const INODE_BITMAP_BLOCKS: u32 = 1;
+/// 文件名的最大长度，与easy-fs中的目录项一致
+const NAME_LENGTH_LIMIT: usize = 27;
+
+/// 用宿主机上的文件模拟块设备
+struct BlockFile(Mutex<File>);
+
+impl BlockDevice for BlockFile {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) {
+        let mut file = self.0.lock().unwrap();
+        file.seek(SeekFrom::Start((block_id * BLOCK_SZ) as u64))
+            .expect("Error when seeking!");
+        assert_eq!(file.read(buf).unwrap(), BLOCK_SZ, "Not a complete block!");
+    }
+
+    fn write_block(&self, block_id: usize, buf: &[u8]) {
+        let mut file = self.0.lock().unwrap();
+        file.seek(SeekFrom::Start((block_id * BLOCK_SZ) as u64))
+            .expect("Error when seeking!");
+        assert_eq!(file.write(buf).unwrap(), BLOCK_SZ, "Not a complete block!");
+    }
+}
+
+fn main() {
+    let matches = Command::new("fs-pack")
+        .about("Pack user application ELFs into an easy-fs image")
+        .arg(
+            Arg::new("source")
+                .short('s')
+                .long("source")
+                .takes_value(true)
+                .required(true)
+                .help("Directory of application sources, e.g. ../user/src/bin/"),
+        )
+        .arg(
+            Arg::new("target")
+                .short('t')
+                .long("target")
+                .takes_value(true)
+                .required(true)
+                .help("Directory of built ELFs, e.g. ../user/target/riscv64gc-unknown-none-elf/release/"),
+        )
+        .arg(
+            Arg::new("output")
+                .short('o')
+                .long("output")
+                .takes_value(true)
+                .help("Path of the image to write, defaults to <target>/fs.img"),
+        )
+        .get_matches();
+    let src_path = Path::new(matches.value_of("source").unwrap());
+    let target_path = Path::new(matches.value_of("target").unwrap());
+    let img_path = match matches.value_of("output") {
+        Some(output) => Path::new(output).to_path_buf(),
+        None => target_path.join("fs.img"),
+    };
+    match pack(src_path, target_path, &img_path) {
+        Ok(apps) => println!("packed {} apps into {}", apps.len(), img_path.display()),
+        Err(e) => {
+            eprintln!("fs-pack: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// 以`src_path`下的源文件名作为应用名，把`target_path`下同名的ELF写入新建的镜像`img_path`，返回写入的应用名
+fn pack(src_path: &Path, target_path: &Path, img_path: &Path) -> io::Result<Vec<String>> {
+    let mut apps: Vec<String> = Vec::new();
+    for dir_entry in read_dir(src_path)? {
+        let path = dir_entry?.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some("rs") {
+            continue;
+        }
+        let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+        if name.len() > NAME_LENGTH_LIMIT {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "app name `{}` is longer than {} bytes",
+                    name, NAME_LENGTH_LIMIT
+                ),
+            ));
+        }
+        apps.push(name);
+    }
+    apps.sort();
+
+    let f = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(img_path)?;
+    f.set_len((TOTAL_BLOCKS + SWAP_BLOCKS) as u64 * BLOCK_SZ as u64)?;
+    let block_file: Arc<dyn BlockDevice> = Arc::new(BlockFile(Mutex::new(f)));
+    let efs = EasyFileSystem::create(block_file, TOTAL_BLOCKS, INODE_BITMAP_BLOCKS);
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    for app in apps.iter() {
+        let mut all_data: Vec<u8> = Vec::new();
+        File::open(target_path.join(app))
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", app, e)))?
+            .read_to_end(&mut all_data)?;
+        let inode = root_inode.create(app.as_str()).unwrap();
+        if inode.write_at(0, all_data.as_slice()) < all_data.len() {
+            return Err(io::Error::other(format!(
+                "{}: no space left in the file system image",
+                app
+            )));
+        }
+    }
+    Ok(apps)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{create_dir_all, remove_dir_all};
+
+    #[test]
+    fn pack_and_reopen() {
+        let dir = std::env::temp_dir().join("fs-pack-test");
+        let _ = remove_dir_all(&dir);
+        let src = dir.join("src");
+        let target = dir.join("target");
+        create_dir_all(&src).unwrap();
+        create_dir_all(&target).unwrap();
+        // 源文件名决定应用名，其他扩展名的文件会被忽略
+        for (name, len) in [("initproc", 100usize), ("user_shell", 70 * BLOCK_SZ + 3)] {
+            File::create(src.join(format!("{}.rs", name))).unwrap();
+            let data: Vec<u8> = (0..len).map(|i| (i % 251) as u8).collect();
+            File::create(target.join(name))
+                .unwrap()
+                .write_all(&data)
+                .unwrap();
+        }
+        File::create(src.join("README")).unwrap();
+
+        let img = dir.join("fs.img");
+        let apps = pack(&src, &target, &img).unwrap();
+        assert_eq!(apps, vec!["initproc", "user_shell"]);
+
+        let f = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&img)
+            .unwrap();
+        // 文件系统之后是交换区
+        assert_eq!(
+            f.metadata().unwrap().len(),
+            (TOTAL_BLOCKS + SWAP_BLOCKS) as u64 * BLOCK_SZ as u64
+        );
+        let block_file: Arc<dyn BlockDevice> = Arc::new(BlockFile(Mutex::new(f)));
+        let efs = EasyFileSystem::open(block_file);
+        let root_inode = EasyFileSystem::root_inode(&efs);
+        assert_eq!(root_inode.ls(), apps);
+        for app in apps.iter() {
+            let mut expected = Vec::new();
+            File::open(target.join(app))
+                .unwrap()
+                .read_to_end(&mut expected)
+                .unwrap();
+            let inode = root_inode.find(app).unwrap();
+            let mut buf = vec![0u8; expected.len() + BLOCK_SZ];
+            let len = inode.read_at(0, &mut buf);
+            assert_eq!(&buf[..len], expected.as_slice());
+        }
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn missing_elf_is_an_error() {
+        let dir = std::env::temp_dir().join("fs-pack-missing-test");
+        let _ = remove_dir_all(&dir);
+        create_dir_all(&dir).unwrap();
+        File::create(dir.join("hello.rs")).unwrap();
+        assert!(pack(&dir, &dir, &dir.join("fs.img")).is_err());
+        remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,8 +1,14 @@
 cargo build --release
 rust-objcopy --strip-all target/riscv64gc-unknown-none-elf/release/os -O binary target/riscv64gc-unknown-none-elf/release/os.bin
 
+# 构建用户程序并用fs-pack打包成easy-fs镜像，内核启动后从virtio-blk设备上加载应用
+make fs-img
+FS_IMG=../user/target/riscv64gc-unknown-none-elf/release/fs.img
+
 qemu-system-riscv64 \
 	-machine virt \
 	-nographic \
 	-bios ../../bootloader/rustsbi-qemu.bin \
 	-device loader,file=target/riscv64gc-unknown-none-elf/release/os.bin,addr=0x80200000 \
+	-drive file=$FS_IMG,if=none,format=raw,id=x0 \
+	-device virtio-blk-device,drive=x0,bus=virtio-mmio-bus.0
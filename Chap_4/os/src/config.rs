@@ -1,13 +1,33 @@
 //! Constants used in rCore
 
-pub const USER_STACK_SIZE: usize = 4096;
+pub const USER_STACK_SIZE: usize = 4096 * 2;
 pub const KERNEL_STACK_SIZE: usize = 4096 * 2;
-pub const MAX_APP_NUM: usize = 4;
-pub const APP_BASE_ADDRESS: usize = 0x80400000;
-pub const APP_SIZE_LIMIT: usize = 0x20000;
+pub const KERNEL_HEAP_SIZE: usize = 0x30_0000;
 pub const CLOCK_FREQ: usize = 12500000;
-pub const MEMORY_END: usize = 0x80800000     // 整块物理内存的终止地址
+pub const MEMORY_END: usize = 0x80800000;     // 整块物理内存的终止地址
+pub const PAGE_SIZE: usize = 0x1000;
+pub const PAGE_SIZE_BITS: usize = 0xc;
+
 pub const TRAMPOLINE: usize = usize::MAX - PAGE_SIZE + 1;
+pub const TRAP_CONTEXT: usize = TRAMPOLINE - PAGE_SIZE;
+/// SV39中低256GiB是用户程序可以使用的地址，再往上到跳板之前的地址都是不合法的
+pub const USER_SPACE_END: usize = 1 << 38;
+
+/// QEMU virt平台上virtio-mmio块设备寄存器的起始地址
+pub const VIRTIO0: usize = 0x10001000;
+
+/// 交换区在块设备上的起始块号，紧接在fs-pack生成的16MiB文件系统之后
+pub const SWAP_START_BLOCK: usize = 16 * 2048;
+/// 交换区的页面数：8MiB，与fs-pack中的SWAP_BLOCKS一致
+pub const SWAP_PAGES: usize = 2048;
+
+/// sys_task_info统计调用次数的系统调用编号上限
+pub const MAX_SYSCALL_NUM: usize = 500;
+
+/// 需要在内核地址空间中恒等映射的MMIO区间：(起始地址, 长度)
+pub const MMIO: &[(usize, usize)] = &[
+    (VIRTIO0, 0x1000),
+];
 
 // #[cfg(feature = "board_k210")]
 // pub const CLOCK_FREQ: usize = 403000000 / 62;
@@ -16,9 +36,3 @@ pub const TRAMPOLINE: usize = usize::MAX - PAGE_SIZE + 1;
 // pub const CLOCK_FREQ: usize = 12500000;
 
 // pub use crate::board::CLOCK_FREQ;
-
-pub fn kernel_stack_position(app_id: usize) -> (usize, usize) {
-    let top = TRAMPOLINE - app_id * (KERNEL_STACK_SIZE + PAGE_SIZE);
-    let bottom = top - KERNEL_STACK_SIZE;
-    (bottom, top)
-}
\ No newline at end of file
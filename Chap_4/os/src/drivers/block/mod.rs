@@ -0,0 +1,30 @@
+mod virtio_blk;
+
+pub use virtio_blk::VirtIOBlock;
+
+use alloc::sync::Arc;
+use easy_fs::{BlockDevice, BLOCK_SZ};
+use lazy_static::*;
+
+type BlockDeviceImpl = virtio_blk::VirtIOBlock;
+
+lazy_static! {
+    pub static ref BLOCK_DEVICE: Arc<dyn BlockDevice> = Arc::new(BlockDeviceImpl::new());
+}
+
+/// 简单的读写测试，会覆盖磁盘上前512个块的内容
+#[allow(unused)]
+pub fn block_device_test() {
+    let block_device = BLOCK_DEVICE.clone();
+    let mut write_buffer = [0u8; BLOCK_SZ];
+    let mut read_buffer = [0u8; BLOCK_SZ];
+    for i in 0..512 {
+        for byte in write_buffer.iter_mut() {
+            *byte = i as u8;
+        }
+        block_device.write_block(i as usize, &write_buffer);
+        block_device.read_block(i as usize, &mut read_buffer);
+        assert_eq!(write_buffer, read_buffer);
+    }
+    println!("block device test passed!");
+}
@@ -0,0 +1,79 @@
+use easy_fs::BlockDevice;
+use crate::config::VIRTIO0;
+use crate::mm::{
+    frame_alloc_contiguous, kernel_token, FrameTracker, PageTable, PhysAddr, PhysPageNum,
+    StepByOne, VirtAddr,
+};
+use crate::sync::UPSafeCell;
+use alloc::vec::Vec;
+use lazy_static::*;
+use virtio_drivers::{VirtIOBlk, VirtIOHeader};
+
+/// QEMU virt平台上通过virtio-mmio接入的块设备
+pub struct VirtIOBlock(UPSafeCell<VirtIOBlk<'static>>);
+
+lazy_static! {
+    // virtio队列使用的DMA内存，由FrameTracker持有直到驱动归还
+    static ref QUEUE_FRAMES: UPSafeCell<Vec<FrameTracker>> = unsafe { UPSafeCell::new(Vec::new()) };
+}
+
+impl BlockDevice for VirtIOBlock {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) {
+        self.0
+            .exclusive_access()
+            .read_block(block_id, buf)
+            .expect("Error when reading VirtIOBlk");
+    }
+    fn write_block(&self, block_id: usize, buf: &[u8]) {
+        self.0
+            .exclusive_access()
+            .write_block(block_id, buf)
+            .expect("Error when writing VirtIOBlk");
+    }
+}
+
+impl VirtIOBlock {
+    pub fn new() -> Self {
+        unsafe {
+            Self(UPSafeCell::new(
+                VirtIOBlk::new(&mut *(VIRTIO0 as *mut VirtIOHeader)).unwrap(),
+            ))
+        }
+    }
+}
+
+// 以下几个函数是virtio-drivers要求内核提供的接口
+
+/// 分配`pages`个物理上连续的页帧作为DMA缓冲区
+#[no_mangle]
+pub extern "C" fn virtio_dma_alloc(pages: usize) -> PhysAddr {
+    let frames = frame_alloc_contiguous(pages, 1).expect("Cannot allocate DMA frames!");
+    let ppn_base = frames[0].ppn;
+    QUEUE_FRAMES.exclusive_access().extend(frames);
+    ppn_base.into()
+}
+
+#[no_mangle]
+pub extern "C" fn virtio_dma_dealloc(pa: PhysAddr, pages: usize) -> i32 {
+    let mut ppn: PhysPageNum = pa.into();
+    let mut queue_frames = QUEUE_FRAMES.exclusive_access();
+    for _ in 0..pages {
+        // 丢弃对应的FrameTracker，页帧在drop时归还给分配器
+        queue_frames.retain(|frame| frame.ppn != ppn);
+        ppn.step();
+    }
+    0
+}
+
+/// 内核地址空间对物理内存是恒等映射的
+#[no_mangle]
+pub extern "C" fn virtio_phys_to_virt(paddr: PhysAddr) -> VirtAddr {
+    VirtAddr(paddr.0)
+}
+
+#[no_mangle]
+pub extern "C" fn virtio_virt_to_phys(vaddr: VirtAddr) -> PhysAddr {
+    PageTable::from_token(kernel_token())
+        .translate_va(vaddr)
+        .unwrap()
+}
@@ -0,0 +1,254 @@
+//! 内核中的文件：把easy-fs的`Inode`包装成带有读写位置和访问权限的`OSInode`
+use super::{File, FileError, Stat, StatMode, SEEK_CUR, SEEK_END, SEEK_SET};
+use crate::drivers::BLOCK_DEVICE;
+use crate::mm::UserBuffer;
+use crate::sync::UPSafeCell;
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use bitflags::*;
+use easy_fs::{EasyFileSystem, Inode, MAX_FILE_SIZE, NAME_LENGTH_LIMIT};
+use lazy_static::*;
+
+/// 进程打开的一个普通文件
+pub struct OSInode {
+    readable: bool,
+    writable: bool,
+    // 每次写之前都先把读写位置移到文件末尾
+    append: bool,
+    inner: UPSafeCell<OSInodeInner>,
+}
+
+pub struct OSInodeInner {
+    // 当前的读写位置
+    offset: usize,
+    inode: Arc<Inode>,
+}
+
+impl OSInode {
+    pub fn new(readable: bool, writable: bool, inode: Arc<Inode>) -> Self {
+        Self {
+            readable,
+            writable,
+            append: false,
+            inner: unsafe { UPSafeCell::new(OSInodeInner { offset: 0, inode }) },
+        }
+    }
+
+    /// 从当前位置读到文件末尾，加载应用的ELF时使用
+    pub fn read_all(&self) -> Vec<u8> {
+        let mut inner = self.inner.exclusive_access();
+        let mut buffer = [0u8; 512];
+        let mut v: Vec<u8> = Vec::new();
+        loop {
+            let len = inner.inode.read_at(inner.offset, &mut buffer);
+            if len == 0 {
+                break;
+            }
+            inner.offset += len;
+            v.extend_from_slice(&buffer[..len]);
+        }
+        v
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.inner.exclusive_access().inode.is_dir()
+    }
+
+    fn with_append(mut self, append: bool) -> Self {
+        self.append = append;
+        self
+    }
+}
+
+lazy_static! {
+    /// 根目录，内核第一次访问时从块设备上打开文件系统
+    pub static ref ROOT_INODE: Arc<Inode> = {
+        let efs = EasyFileSystem::open(BLOCK_DEVICE.clone());
+        Arc::new(EasyFileSystem::root_inode(&efs))
+    };
+}
+
+/// 把相对于`cwd`的路径`path`规范化为从根目录出发的各级名字，"."和".."在这里就被消去。
+/// 目录不能有硬链接，所以按字面处理".."和沿着磁盘上的".."目录项走到的是同一个目录
+pub fn resolve_path(cwd: &str, path: &str) -> Vec<String> {
+    let mut components: Vec<String> = Vec::new();
+    let full = if path.starts_with('/') {
+        [path, ""]
+    } else {
+        [cwd, path]
+    };
+    for name in full.iter().flat_map(|part| part.split('/')) {
+        match name {
+            "" | "." => {}
+            ".." => {
+                // 根目录的".."还是根目录
+                components.pop();
+            }
+            _ => components.push(name.to_string()),
+        }
+    }
+    components
+}
+
+/// 各级名字拼成的绝对路径
+pub fn path_string(components: &[String]) -> String {
+    if components.is_empty() {
+        return String::from("/");
+    }
+    let mut path = String::new();
+    for name in components {
+        path.push('/');
+        path.push_str(name);
+    }
+    path
+}
+
+/// 从根目录出发逐级查找，中间某一级不存在或者不是目录时返回None
+pub fn find_inode(components: &[String]) -> Option<Arc<Inode>> {
+    let mut inode = ROOT_INODE.clone();
+    for name in components {
+        if !inode.is_dir() {
+            return None;
+        }
+        inode = inode.find(name)?;
+    }
+    Some(inode)
+}
+
+/// 打印根目录下的所有文件，内核启动时调用，方便在shell中按名字运行
+pub fn list_apps() {
+    println!("/**** APPS ****");
+    for app in ROOT_INODE.ls() {
+        println!("{}", app);
+    }
+    println!("**************/");
+}
+
+bitflags! {
+    /// 打开文件时的选项，取值和Linux一致
+    pub struct OpenFlags: u32 {
+        const RDONLY = 0;
+        const WRONLY = 1 << 0;
+        const RDWR = 1 << 1;
+        const CREATE = 0o100;
+        const TRUNC = 0o1000;
+        const APPEND = 0o2000;
+    }
+}
+
+impl OpenFlags {
+    /// 返回(readable, writable)
+    pub fn read_write(&self) -> (bool, bool) {
+        if !self.intersects(Self::WRONLY | Self::RDWR) {
+            (true, false)
+        } else if self.contains(Self::WRONLY) {
+            (false, true)
+        } else {
+            (true, true)
+        }
+    }
+}
+
+/// 打开相对于`cwd`的文件，文件不存在且没有指定CREATE时返回None
+pub fn open_file(cwd: &str, path: &str, flags: OpenFlags) -> Option<Arc<OSInode>> {
+    let (readable, writable) = flags.read_write();
+    let components = resolve_path(cwd, path);
+    let (name, parent) = match components.split_last() {
+        Some((name, parent)) => (name, find_inode(parent)?),
+        // 根目录
+        None => return Some(Arc::new(OSInode::new(readable, writable, ROOT_INODE.clone()))),
+    };
+    if !parent.is_dir() || name.len() > NAME_LENGTH_LIMIT {
+        return None;
+    }
+    let inode = match parent.find(name) {
+        // 目录只能以只读方式打开
+        Some(inode) if inode.is_dir() && writable => return None,
+        Some(inode) => {
+            if flags.contains(OpenFlags::TRUNC) && !inode.is_dir() {
+                inode.clear();
+            }
+            inode
+        }
+        None if flags.contains(OpenFlags::CREATE) => parent.create(name)?,
+        None => return None,
+    };
+    Some(Arc::new(
+        OSInode::new(readable, writable, inode).with_append(flags.contains(OpenFlags::APPEND)),
+    ))
+}
+
+impl File for OSInode {
+    fn readable(&self) -> bool {
+        self.readable
+    }
+    fn writable(&self) -> bool {
+        self.writable
+    }
+    fn read(&self, mut buf: UserBuffer) -> usize {
+        let mut inner = self.inner.exclusive_access();
+        let mut total_read_size = 0usize;
+        for slice in buf.buffers.iter_mut() {
+            let read_size = inner.inode.read_at(inner.offset, slice);
+            if read_size == 0 {
+                break;
+            }
+            inner.offset += read_size;
+            total_read_size += read_size;
+        }
+        total_read_size
+    }
+    /// 超出最大文件大小或者磁盘已满时只写入一部分，一个字节都没有写入时返回Err
+    fn write(&self, buf: UserBuffer) -> Result<usize, FileError> {
+        let mut inner = self.inner.exclusive_access();
+        if self.append {
+            inner.offset = inner.inode.size();
+        }
+        if buf.len() > 0 && inner.offset >= MAX_FILE_SIZE {
+            return Err(FileError::FileTooLarge);
+        }
+        let mut total_write_size = 0usize;
+        for slice in buf.buffers.iter() {
+            let write_size = inner.inode.write_at(inner.offset, slice);
+            inner.offset += write_size;
+            total_write_size += write_size;
+            if write_size < slice.len() {
+                break;
+            }
+        }
+        if buf.len() > 0 && total_write_size == 0 {
+            return Err(FileError::NoSpace);
+        }
+        Ok(total_write_size)
+    }
+    fn seek(&self, offset: isize, whence: usize) -> Option<usize> {
+        let mut inner = self.inner.exclusive_access();
+        let base = match whence {
+            SEEK_SET => 0,
+            SEEK_CUR => inner.offset as isize,
+            SEEK_END => inner.inode.size() as isize,
+            _ => return None,
+        };
+        let new_offset = base.checked_add(offset)?;
+        if new_offset < 0 || new_offset as usize > MAX_FILE_SIZE {
+            return None;
+        }
+        // 允许移动到文件末尾之后（但不超过最大文件大小），之后的写操作会把文件扩大到那里
+        inner.offset = new_offset as usize;
+        Some(inner.offset)
+    }
+    fn stat(&self) -> Option<Stat> {
+        let inner = self.inner.exclusive_access();
+        let ino = inner.inode.inode_id();
+        let mode = if inner.inode.is_dir() {
+            StatMode::DIR
+        } else {
+            StatMode::FILE
+        };
+        Some(Stat::new(ino as u64, mode, inner.inode.nlink()))
+    }
+    fn inode(&self) -> Option<Arc<Inode>> {
+        Some(self.inner.exclusive_access().inode.clone())
+    }
+}
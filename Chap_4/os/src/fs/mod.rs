@@ -0,0 +1,89 @@
+//! 文件抽象：进程通过文件描述符访问的一切对象（标准输入输出、管道等）都实现了File Trait
+mod inode;
+mod pipe;
+mod stdio;
+
+use crate::mm::UserBuffer;
+use alloc::sync::Arc;
+
+pub trait File: Send + Sync {
+    fn readable(&self) -> bool;
+    fn writable(&self) -> bool;
+    /// 从文件读取数据填充到用户缓冲区，返回实际读取的字节数
+    fn read(&self, buf: UserBuffer) -> usize;
+    /// 把用户缓冲区中的数据写入文件，返回实际写入的字节数；一个字节都没有写入就出错时返回Err
+    fn write(&self, buf: UserBuffer) -> Result<usize, FileError>;
+    /// 移动读写位置，返回新的位置；管道、标准输入输出等不支持定位的文件返回None
+    fn seek(&self, _offset: isize, _whence: usize) -> Option<usize> {
+        None
+    }
+    /// 获取文件的元数据，只有文件系统中的文件支持
+    fn stat(&self) -> Option<Stat> {
+        None
+    }
+    /// 文件系统中的文件对应的索引节点，mmap时使用
+    fn inode(&self) -> Option<Arc<Inode>> {
+        None
+    }
+}
+
+/// 文件读写出错的原因，由系统调用转换成对应的错误码
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileError {
+    /// 管道的所有读端都已经关闭
+    BrokenPipe,
+    /// 写入位置超出了文件的最大大小
+    FileTooLarge,
+    /// 磁盘已满
+    NoSpace,
+}
+
+/// 从文件开头计算偏移
+pub const SEEK_SET: usize = 0;
+/// 从当前位置计算偏移
+pub const SEEK_CUR: usize = 1;
+/// 从文件末尾计算偏移
+pub const SEEK_END: usize = 2;
+
+/// 文件的元数据，内存布局与用户库中的定义一致
+#[repr(C)]
+#[derive(Debug)]
+pub struct Stat {
+    /// 文件所在的设备号，目前只有一个块设备，始终为0
+    pub dev: u64,
+    /// 索引节点编号
+    pub ino: u64,
+    /// 文件类型
+    pub mode: StatMode,
+    /// 硬链接数
+    pub nlink: u32,
+    pad: [u64; 7],
+}
+
+impl Stat {
+    pub fn new(ino: u64, mode: StatMode, nlink: u32) -> Self {
+        Self {
+            dev: 0,
+            ino,
+            mode,
+            nlink,
+            pad: [0; 7],
+        }
+    }
+}
+
+bitflags! {
+    /// 文件类型，取值和Linux一致
+    pub struct StatMode: u32 {
+        const NULL = 0;
+        /// 目录
+        const DIR = 0o040000;
+        /// 普通文件
+        const FILE = 0o100000;
+    }
+}
+
+pub use easy_fs::{Inode, NAME_LENGTH_LIMIT};
+pub use inode::{find_inode, list_apps, open_file, path_string, resolve_path, OSInode, OpenFlags};
+pub use pipe::{make_pipe, Pipe};
+pub use stdio::{Stderr, Stdin, Stdout};
@@ -0,0 +1,90 @@
+use super::{File, FileError};
+use crate::mm::UserBuffer;
+use crate::sbi::{console_getchar, console_putchar};
+use crate::task::{current_killed, suspend_current_and_run_next};
+
+/// 标准输入，只读
+pub struct Stdin;
+
+/// 标准输出，只写
+pub struct Stdout;
+
+/// 标准错误输出，和标准输出一样写到串口上
+pub struct Stderr;
+
+impl File for Stdin {
+    fn readable(&self) -> bool {
+        true
+    }
+    fn writable(&self) -> bool {
+        false
+    }
+    /// 每次只读入一个字符，串口上还没有输入时让出CPU
+    fn read(&self, mut user_buf: UserBuffer) -> usize {
+        if user_buf.len() == 0 {
+            return 0;
+        }
+        let mut c: usize;
+        loop {
+            c = console_getchar();
+            // 串口上还没有输入时（SBI返回0或-1），让出CPU之后再来查询
+            if c == 0 || c == usize::MAX {
+                suspend_current_and_run_next();
+                if current_killed() {
+                    return 0;
+                }
+                continue;
+            } else {
+                break;
+            }
+        }
+        let ch = c as u8;
+        unsafe {
+            user_buf.buffers[0].as_mut_ptr().write_volatile(ch);
+        }
+        1
+    }
+    fn write(&self, _user_buf: UserBuffer) -> Result<usize, FileError> {
+        panic!("Cannot write to stdin!");
+    }
+}
+
+/// 按字节原样输出到串口，不要求是合法的UTF-8，跨页面被拆开的多字节字符也能正确输出
+fn print_user_buffer(user_buf: &UserBuffer) -> usize {
+    for buffer in user_buf.buffers.iter() {
+        for &byte in buffer.iter() {
+            console_putchar(byte as usize);
+        }
+    }
+    user_buf.len()
+}
+
+impl File for Stdout {
+    fn readable(&self) -> bool {
+        false
+    }
+    fn writable(&self) -> bool {
+        true
+    }
+    fn read(&self, _user_buf: UserBuffer) -> usize {
+        panic!("Cannot read from stdout!");
+    }
+    fn write(&self, user_buf: UserBuffer) -> Result<usize, FileError> {
+        Ok(print_user_buffer(&user_buf))
+    }
+}
+
+impl File for Stderr {
+    fn readable(&self) -> bool {
+        false
+    }
+    fn writable(&self) -> bool {
+        true
+    }
+    fn read(&self, _user_buf: UserBuffer) -> usize {
+        panic!("Cannot read from stderr!");
+    }
+    fn write(&self, user_buf: UserBuffer) -> Result<usize, FileError> {
+        Ok(print_user_buffer(&user_buf))
+    }
+}
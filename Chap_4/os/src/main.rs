@@ -7,10 +7,12 @@
 
 #[macro_use]
 mod console;
+mod drivers;
+mod fs;
 mod sbi;
 mod lang_items;
 mod sync;
-mod loader;
+mod mm;
 mod timer;
 pub mod config;
 pub mod syscall;
@@ -18,22 +20,25 @@ pub mod trap;
 pub mod task;
 
 extern crate alloc;
+#[macro_use]
 extern crate bitflags;
 
 use core::arch::global_asm;
 global_asm!(include_str!("entry.asm"));
-global_asm!(include_str!("link_app.S"));    // 用以将应用程序静态链接到内核里
 
 #[no_mangle]
 pub fn rust_main() -> !{
     clear_bss();
     println!("[kernel] Hello, world");
-    trap::init();                         // 将trap上下文保存在内核栈上， 所有程序共享一个trap上下文
-    loader::load_apps();
+    mm::init();                           // 初始化堆、物理页帧分配器，并开启分页
+    mm::remap_test();
+    trap::init();
     trap::enable_timer_interrupt();    // 设置sie.stie使得S特权级时钟中断不会被屏蔽
     timer::set_next_trigger();
-    task::run_first_task();
-    panic!("[kernel] Finish!");
+    task::add_initproc();
+    fs::list_apps();
+    task::run_tasks();
+    panic!("Unreachable in rust_main!");
 }
 
 fn clear_bss() {
@@ -0,0 +1,93 @@
+//! ASID：satp中的地址空间标识，TLB项带有ASID标签，切换地址空间时不需要刷新整个TLB。
+//! ASID 0留给内核地址空间；ASID分配完了或者硬件不支持ASID时，用户地址空间也只能使用ASID 0，
+//! 这时切换到它或者从它切换回内核都要刷新整个TLB（见trap.S）
+use crate::sync::UPSafeCell;
+use alloc::vec::Vec;
+use core::arch::asm;
+use lazy_static::*;
+use riscv::register::satp;
+
+/// satp中ASID字段的位置
+pub const ASID_SHIFT: usize = 44;
+const ASID_MASK: usize = 0xffff;
+
+/// 和PidAllocator一样，优先复用回收的ASID
+struct AsidAllocator {
+    current: usize,
+    max: usize,         // 硬件支持的最大ASID，为0时不支持ASID
+    recycled: Vec<usize>,
+}
+
+impl AsidAllocator {
+    fn alloc(&mut self) -> usize {
+        let asid = if let Some(asid) = self.recycled.pop() {
+            asid
+        } else if self.current > self.max {
+            return 0;
+        } else {
+            self.current += 1;
+            self.current - 1
+        };
+        // 之前使用这个ASID的地址空间可能还在TLB中留有表项
+        flush_asid(asid);
+        asid
+    }
+    fn dealloc(&mut self, asid: usize) {
+        if asid >= self.current || self.recycled.contains(&asid) {
+            panic!("ASID {} has not been allocated!", asid);
+        }
+        self.recycled.push(asid);
+    }
+}
+
+lazy_static! {
+    static ref ASID_ALLOCATOR: UPSafeCell<AsidAllocator> = unsafe {
+        UPSafeCell::new(AsidAllocator {
+            current: 1,
+            max: 0,
+            recycled: Vec::new(),
+        })
+    };
+}
+
+/// 探测硬件支持的ASID位数：向satp的ASID字段写入全1，读回来的值就是最大的ASID。
+/// 必须在开启分页之后调用
+pub fn init_asid() {
+    if cfg!(feature = "no_asid") {
+        println!("[kernel] ASID disabled");
+        return;
+    }
+    let old = satp::read().bits();
+    unsafe {
+        satp::write(old | (ASID_MASK << ASID_SHIFT));
+    }
+    let max = (satp::read().bits() >> ASID_SHIFT) & ASID_MASK;
+    unsafe {
+        satp::write(old);
+        asm!("sfence.vma");
+    }
+    ASID_ALLOCATOR.exclusive_access().max = max;
+    println!("[kernel] ASID supported up to {}", max);
+}
+
+/// ASID的RAII句柄，drop时回收。ASID为0时表示没有分到ASID
+pub struct AsidHandle(pub usize);
+
+impl Drop for AsidHandle {
+    fn drop(&mut self) {
+        if self.0 != 0 {
+            ASID_ALLOCATOR.exclusive_access().dealloc(self.0);
+        }
+    }
+}
+
+pub fn asid_alloc() -> AsidHandle {
+    AsidHandle(ASID_ALLOCATOR.exclusive_access().alloc())
+}
+
+/// 刷新TLB中属于asid的所有表项
+fn flush_asid(asid: usize) {
+    unsafe {
+        asm!("sfence.vma zero, {}", in(reg) asid);
+    }
+}
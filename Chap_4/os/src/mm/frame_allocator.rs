@@ -0,0 +1,224 @@
+use super::{PhysAddr, PhysPageNum};
+use crate::config::MEMORY_END;
+use crate::sync::UPSafeCell;
+#[cfg(feature = "buddy_frame_allocator")]
+use alloc::collections::BTreeSet;
+use alloc::vec::Vec;
+use core::fmt::{self, Debug, Formatter};
+use lazy_static::*;
+
+trait FrameAllocator {
+    fn new() -> Self;
+    fn alloc(&mut self) -> Option<PhysPageNum>;
+    /// 分配n个物理上连续的页帧，起始物理页号是align的倍数（align为2的幂），
+    /// 之后每个页帧都单独通过dealloc回收
+    fn alloc_contiguous(&mut self, n: usize, align: usize) -> Option<PhysPageNum>;
+    fn dealloc(&mut self, ppn: PhysPageNum);
+}
+
+#[cfg_attr(feature = "buddy_frame_allocator", allow(dead_code))]
+pub struct StackFrameAllocator {
+    current: usize,    // 空闲内存的起始物理页号
+    end: usize,        // 空闲内存的结束物理页号
+    recycled: Vec<usize>,
+}
+
+impl FrameAllocator for StackFrameAllocator {
+    fn new() -> Self {
+        Self {
+            current: 0,
+            end: 0,
+            recycled: Vec::new(),
+        }
+    }
+    fn alloc(&mut self) -> Option<PhysPageNum> {
+        if let Some(ppn) = self.recycled.pop() {     // 率先检查有没有回收的物理页帧，有的话直接从里面pop出来一个
+            Some(ppn.into())
+        } else{
+            if self.current == self.end {
+                None
+            } else{
+                self.current += 1;
+                Some((self.current - 1).into())     // 用into的方法将usize转化挣了物理页帧号
+            }
+        }
+    }
+    /// 只能从还没有分配过的区域中切出一段，为了对齐而跳过的页帧直接放入recycled
+    fn alloc_contiguous(&mut self, n: usize, align: usize) -> Option<PhysPageNum> {
+        let start = (self.current + align - 1) & !(align - 1);
+        if start + n > self.end {
+            return None;
+        }
+        self.recycled.extend(self.current..start);
+        self.current = start + n;
+        Some(start.into())
+    }
+    fn dealloc(&mut self, ppn: PhysPageNum) {
+        let ppn = ppn.0;
+        if ppn >= self.current || self.recycled
+            .iter()
+            .find(|&v| {*v == ppn})
+            .is_some(){      // 用is_some是因为find会返回一个Option<T>
+                panic!("Frame ppn = {:#x} has not been allocated!", ppn);
+        }
+        self.recycled.push(ppn);
+    }
+}
+
+#[cfg_attr(feature = "buddy_frame_allocator", allow(dead_code))]
+impl StackFrameAllocator {
+    pub fn init(&mut self, l: PhysPageNum, r: PhysPageNum){
+        self.current = l.0;
+        self.end = r.0;
+    }
+}
+
+/// 伙伴系统中最大的块为2^(BUDDY_MAX_ORDER - 1)个页帧
+#[cfg(feature = "buddy_frame_allocator")]
+const BUDDY_MAX_ORDER: usize = 20;
+
+/// 伙伴系统：空闲内存按2的幂大小、按自身大小对齐的块来管理，每一阶的空闲块放在一个有序集合中，
+/// 分配和回收都是O(log n)的。分配时把大块一分为二直到大小合适，回收时和空闲的伙伴块合并
+#[cfg(feature = "buddy_frame_allocator")]
+pub struct BuddyFrameAllocator {
+    free_lists: Vec<BTreeSet<usize>>,    // free_lists[k]中是所有大小为2^k的空闲块的起始物理页号
+}
+
+#[cfg(feature = "buddy_frame_allocator")]
+impl BuddyFrameAllocator {
+    pub fn init(&mut self, l: PhysPageNum, r: PhysPageNum) {
+        let (mut l, r) = (l.0, r.0);
+        // 切成尽可能大的对齐块
+        while l < r {
+            let mut order = 0;
+            while order + 1 < BUDDY_MAX_ORDER
+                && l % (1 << (order + 1)) == 0
+                && l + (1 << (order + 1)) <= r
+            {
+                order += 1;
+            }
+            self.free_lists[order].insert(l);
+            l += 1 << order;
+        }
+    }
+    /// 分配一个大小为2^order的块
+    fn alloc_order(&mut self, order: usize) -> Option<usize> {
+        let mut k = (order..BUDDY_MAX_ORDER).find(|k| !self.free_lists[*k].is_empty())?;
+        let block = *self.free_lists[k].iter().next().unwrap();
+        self.free_lists[k].remove(&block);
+        // 后一半作为空闲块放回低一阶
+        while k > order {
+            k -= 1;
+            self.free_lists[k].insert(block + (1 << k));
+        }
+        Some(block)
+    }
+}
+
+#[cfg(feature = "buddy_frame_allocator")]
+impl FrameAllocator for BuddyFrameAllocator {
+    fn new() -> Self {
+        Self {
+            free_lists: (0..BUDDY_MAX_ORDER).map(|_| BTreeSet::new()).collect(),
+        }
+    }
+    fn alloc(&mut self) -> Option<PhysPageNum> {
+        self.alloc_order(0).map(|ppn| ppn.into())
+    }
+    /// 分配一个能放下n个页帧且满足对齐要求的块，多出来的页帧立即回收
+    fn alloc_contiguous(&mut self, n: usize, align: usize) -> Option<PhysPageNum> {
+        let order = n.max(align).next_power_of_two().trailing_zeros() as usize;
+        if n == 0 || order >= BUDDY_MAX_ORDER {
+            return None;
+        }
+        let block = self.alloc_order(order)?;
+        for ppn in block + n..block + (1 << order) {
+            self.dealloc(ppn.into());
+        }
+        Some(block.into())
+    }
+    fn dealloc(&mut self, ppn: PhysPageNum) {
+        let mut block = ppn.0;
+        if (0..BUDDY_MAX_ORDER).any(|k| self.free_lists[k].contains(&(block & !((1 << k) - 1)))) {
+            panic!("Frame ppn = {:#x} has not been allocated!", block);
+        }
+        let mut order = 0;
+        // 伙伴块也空闲时合并成高一阶的块
+        while order + 1 < BUDDY_MAX_ORDER && self.free_lists[order].remove(&(block ^ (1 << order))) {
+            block &= !(1 << order);
+            order += 1;
+        }
+        self.free_lists[order].insert(block);
+    }
+}
+
+// 创建一个全局实例，启用buddy_frame_allocator特性时使用伙伴系统
+#[cfg(not(feature = "buddy_frame_allocator"))]
+type FrameAllocatorImpl = StackFrameAllocator;
+#[cfg(feature = "buddy_frame_allocator")]
+type FrameAllocatorImpl = BuddyFrameAllocator;
+lazy_static!{
+    pub static ref FRAME_ALLOCATOR: UPSafeCell<FrameAllocatorImpl> = unsafe {
+        UPSafeCell::new(FrameAllocatorImpl::new())
+    };
+}
+
+pub fn init_frame_allocator() {
+    extern "C" {
+        fn ekernel();
+    }
+    FRAME_ALLOCATOR
+        .exclusive_access()
+        .init(PhysAddr::from(ekernel as usize).ceil(), PhysAddr::from(MEMORY_END).floor());
+}
+
+pub struct FrameTracker {
+    pub ppn: PhysPageNum,
+}
+
+impl FrameTracker {
+    pub fn new(ppn: PhysPageNum) -> Self {
+        let bytes_array = ppn.get_bytes_array();
+        for i in bytes_array {
+            *i = 0;      // 零初始化
+        }
+        Self { ppn }
+    }
+}
+
+impl Debug for FrameTracker {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_fmt(format_args!("FrameTracker:PPN={:#x}", self.ppn.0))
+    }
+}
+
+impl Drop for FrameTracker{      // 当一个FrameTracker实例被回收的时候，自动将物理页帧回收使用
+    fn drop(&mut self){
+        frame_dealloc(self.ppn);
+    }
+}
+
+/// 物理页帧已经分配完了
+#[derive(Debug)]
+pub struct OutOfMemory;
+
+pub fn frame_alloc() -> Option<FrameTracker> {
+    FRAME_ALLOCATOR
+        .exclusive_access()
+        .alloc()
+        .map(|ppn| FrameTracker::new(ppn))
+}
+
+/// 分配n个物理上连续的页帧，起始物理页号是align的倍数，用于DMA缓冲区等
+pub fn frame_alloc_contiguous(n: usize, align: usize) -> Option<Vec<FrameTracker>> {
+    let start = FRAME_ALLOCATOR
+        .exclusive_access()
+        .alloc_contiguous(n, align)?;
+    Some((0..n).map(|i| FrameTracker::new(PhysPageNum(start.0 + i))).collect())
+}
+
+pub fn frame_dealloc(ppn: PhysPageNum) {
+    FRAME_ALLOCATOR
+        .exclusive_access()
+        .dealloc(ppn);
+}
@@ -1,18 +1,70 @@
+use super::asid::{asid_alloc, AsidHandle};
+use super::swap::{swap_full, swap_out, SwapSlot};
+use super::{frame_alloc, FrameTracker, OutOfMemory};
+use super::{HugePage, PageTable, PageTableEntry, PTEFlags};
+use super::{PhysAddr, PhysPageNum, StepByOne, VPNRange, VirtAddr, VirtPageNum};
+use crate::config::{
+    MEMORY_END, MMIO, PAGE_SIZE, TRAMPOLINE, TRAP_CONTEXT, USER_SPACE_END, USER_STACK_SIZE,
+};
+use crate::fs::Inode;
+use crate::sync::UPSafeCell;
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use bitflags::*;
+use core::arch::asm;
+use lazy_static::*;
+use riscv::register::satp;
 
 pub struct MapArea {
     vpn_range: VPNRange,
-    data_frames: BTreeMap<VirtPageNum, FrameTracker>,   // 该逻辑段中，每个虚拟页面和它被映射到的物理页帧的一个键值对容器
+    data_frames: BTreeMap<VirtPageNum, Arc<FrameTracker>>,   // 该逻辑段中，每个虚拟页面和它被映射到的物理页帧的一个键值对容器，fork之后的物理页帧可能被多个进程共享
     map_type: MapType,
     map_perm: MapPermission,
+    file: Option<FileMapping>,     // 文件映射的逻辑段对应的文件区域
+    swapped: BTreeMap<VirtPageNum, Arc<SwapSlot>>,   // 被换出到交换区的页面，fork之后槽位可能被多个进程共享
+}
+
+#[derive(Clone)]
+pub struct FileMapping {
+    inode: Arc<Inode>,
+    offset: usize,        // 逻辑段的第一页在文件中的偏移
+    shared: bool,         // MAP_SHARED：写入的内容会被写回文件。fork时已经在内存中的页面父子进程共享同一份物理页帧，
+                          // 之后各自读入的页面是不同的物理页帧，彼此的修改要等msync或munmap写回文件后才能看到
 }
 
 #[derive(Copy, Clone, PartialEq, Debug)]
 pub enum MapType {
     Identical,       // 表示恒等映射方式？
     Framed,
+    Lazy,            // 和Framed一样映射到新分配的物理页帧，但要等到第一次访问触发缺页异常时才分配
+    File,            // 和Lazy一样按需分配，分配时从文件中读入页面的内容
+}
+
+/// 触发缺页异常的访问类型
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Access {
+    Read,
+    Write,
+    Exec,            // 取指
 }
 
-MapPermission {      // 是PTEFlags的子集
+/// 内核代替用户程序访问用户内存失败的原因
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum UserAccessError {
+    /// 地址不在任何逻辑段中，或者逻辑段的权限不允许这样访问
+    Fault,
+    /// 物理内存不足
+    OutOfMemory,
+}
+
+impl From<OutOfMemory> for UserAccessError {
+    fn from(_: OutOfMemory) -> Self {
+        UserAccessError::OutOfMemory
+    }
+}
+
+bitflags! {      // 是PTEFlags的子集
     pub struct MapPermission: u8 {
         const R = 1 << 1;
         const W = 1 << 2;
@@ -23,35 +75,424 @@ MapPermission {      // 是PTEFlags的子集
 
 pub struct MemorySet {
     page_table: PageTable,
+    _asid: AsidHandle,             // ASID已经记录在页表中，地址空间被回收时随之回收
     areas: Vec<MapArea>,
+    clock_hand: VirtPageNum,       // Clock算法的指针，下一次从这个页面开始寻找换出的页面
+    pinned: VPNRange,              // 内核正在代替用户程序访问的页面，不能被换出
 }
 
 impl MemorySet{
-    pub fn new_bare() -> Self {
-        Self {
-            page_table: PageTable::new(),
+    /// 用户地址空间，分配一个新的ASID
+    pub fn new_bare() -> Result<Self, OutOfMemory> {
+        Self::new_with_asid(asid_alloc())
+    }
+    fn new_with_asid(asid: AsidHandle) -> Result<Self, OutOfMemory> {
+        Ok(Self {
+            page_table: PageTable::new(asid.0)?,
+            _asid: asid,
             areas: Vec::new(),
-        }
+            clock_hand: VirtPageNum(0),
+            pinned: VPNRange::new(VirtPageNum(0), VirtPageNum(0)),
+        })
+    }
+    pub fn token(&self) -> usize {
+        self.page_table.token()
     }
-    fn push(&mut self, mut map_area: MapArea, data: Option<&[u8]>){
-        map_area.map(&mut self.page_table);
+    /// 物理内存不足时，已经映射的页面会被解除映射，地址空间保持不变
+    fn push(&mut self, mut map_area: MapArea, data: Option<&[u8]>) -> Result<(), OutOfMemory> {
+        if let Err(err) = self.map_area(&mut map_area) {
+            map_area.unmap(&mut self.page_table);
+            return Err(err);
+        }
         if let Some(data) = data {
             map_area.copy_data(&mut self.page_table, data);
         }
         self.areas.push(map_area);
+        Ok(())
+    }
+    fn map_area(&mut self, map_area: &mut MapArea) -> Result<(), OutOfMemory> {
+        if map_area.map_type == MapType::Framed {
+            // 逐页分配，物理内存不足时可以先换出这个地址空间中已有的页面
+            for vpn in map_area.vpn_range {
+                let frame = self.alloc_frame()?;
+                map_area.map_one_with(&mut self.page_table, vpn, frame)?;
+            }
+            Ok(())
+        } else {
+            map_area.map(&mut self.page_table)
+        }
     }
     pub fn insert_framed_area(
         &mut self,
         start_va: VirtAddr, end_va: VirtAddr, permission: MapPermission
-    ){
+    ) -> Result<(), OutOfMemory> {
         self.push(MapArea::new(
             start_va,
             end_va,
             MapType::Framed,
             permission,
-        ), None);
+        ), None)
+    }
+    /// 解除以start_vpn开头的逻辑段的映射并将其移除，逻辑段持有的物理页帧随之回收
+    pub fn remove_area_with_start_vpn(&mut self, start_vpn: VirtPageNum) {
+        if let Some((idx, area)) = self
+            .areas
+            .iter_mut()
+            .enumerate()
+            .find(|(_, area)| area.vpn_range.get_start() == start_vpn)
+        {
+            area.unmap(&mut self.page_table);
+            self.areas.remove(idx);
+        }
+    }
+    /// 把以start开头的逻辑段缩短到new_end为止，被截掉的页面解除映射，用于sbrk
+    pub fn shrink_to(&mut self, start: VirtAddr, new_end: VirtAddr) -> bool {
+        if let Some(area) = self
+            .areas
+            .iter_mut()
+            .find(|area| area.vpn_range.get_start() == start.floor())
+        {
+            area.shrink_to(&mut self.page_table, new_end.ceil());
+            true
+        } else {
+            false
+        }
+    }
+    /// 把以start开头的逻辑段延长到new_end为止，用于sbrk。延长的部分和其他逻辑段重叠，
+    /// 或者物理内存不足时返回false
+    pub fn append_to(&mut self, start: VirtAddr, new_end: VirtAddr) -> bool {
+        let new_end_vpn = new_end.ceil();
+        if let Some(area) = self
+            .areas
+            .iter()
+            .find(|area| area.vpn_range.get_start() == start.floor())
+        {
+            let old_end_vpn = area.vpn_range.get_end();
+            if old_end_vpn < new_end_vpn && self.overlaps(old_end_vpn, new_end_vpn) {
+                return false;
+            }
+        }
+        if let Some(area) = self
+            .areas
+            .iter_mut()
+            .find(|area| area.vpn_range.get_start() == start.floor())
+        {
+            area.append_to(&mut self.page_table, new_end.ceil()).is_ok()
+        } else {
+            false
+        }
+    }
+    /// [start_vpn, end_vpn)中是否有页面属于某个已有的逻辑段
+    fn overlaps(&self, start_vpn: VirtPageNum, end_vpn: VirtPageNum) -> bool {
+        self.areas
+            .iter()
+            .any(|area| area.overlaps(start_vpn, end_vpn))
+    }
+    /// 用于mmap：映射一段新的内存，和已有的逻辑段重叠时返回Ok(false)
+    pub fn mmap(&mut self, start: VirtAddr, end: VirtAddr, permission: MapPermission) -> Result<bool, OutOfMemory> {
+        if self.overlaps(start.floor(), end.ceil()) {
+            return Ok(false);
+        }
+        self.insert_framed_area(start, end, permission)?;
+        Ok(true)
+    }
+    /// 用于mmap：把文件从offset开始的内容映射到[start, end)，页面在第一次访问时从文件读入。
+    /// 和已有的逻辑段重叠时返回false
+    pub fn mmap_file(
+        &mut self,
+        start: VirtAddr,
+        end: VirtAddr,
+        permission: MapPermission,
+        inode: Arc<Inode>,
+        offset: usize,
+        shared: bool,
+    ) -> bool {
+        if self.overlaps(start.floor(), end.ceil()) {
+            return false;
+        }
+        let mut map_area = MapArea::new(start, end, MapType::File, permission);
+        map_area.file = Some(FileMapping { inode, offset, shared });
+        // 文件映射的页面都在访问时才分配，这里不会失败
+        self.areas.push(map_area);
+        true
+    }
+    /// [start_vpn, end_vpn)中的每一页是否都属于某个用户可以访问的逻辑段
+    fn covered_by_user_areas(&self, start_vpn: VirtPageNum, end_vpn: VirtPageNum) -> bool {
+        VPNRange::new(start_vpn, end_vpn).into_iter().all(|vpn| {
+            self.areas
+                .iter()
+                .any(|area| area.contains(vpn) && area.map_perm.contains(MapPermission::U))
+        })
+    }
+    /// 用于msync：把[start, end)中MAP_SHARED文件映射被写过的页面写回文件，
+    /// 范围内有不属于任何逻辑段的页面时返回false
+    pub fn msync(&mut self, start: VirtAddr, end: VirtAddr) -> bool {
+        let start_vpn = start.floor();
+        let end_vpn = end.ceil();
+        if !self.covered_by_user_areas(start_vpn, end_vpn) {
+            return false;
+        }
+        for area in self.areas.iter() {
+            area.sync(&mut self.page_table, start_vpn, end_vpn);
+        }
+        true
+    }
+    /// 把所有MAP_SHARED文件映射被写过的页面写回文件，进程退出或者exec时调用
+    pub fn sync_all(&mut self) {
+        for area in self.areas.iter() {
+            let (start_vpn, end_vpn) = (area.vpn_range.get_start(), area.vpn_range.get_end());
+            area.sync(&mut self.page_table, start_vpn, end_vpn);
+        }
+    }
+    /// 用于munmap：解除[start, end)的映射，范围内的每一页都必须属于某个用户可以访问的逻辑段，
+    /// 只有一部分在范围内的逻辑段会被拆开，范围之外的部分保持不变
+    pub fn munmap(&mut self, start: VirtAddr, end: VirtAddr) -> bool {
+        let start_vpn = start.floor();
+        let end_vpn = end.ceil();
+        if !self.covered_by_user_areas(start_vpn, end_vpn) {
+            return false;
+        }
+        let areas = core::mem::take(&mut self.areas);
+        for mut area in areas {
+            if !area.overlaps(start_vpn, end_vpn) {
+                self.areas.push(area);
+                continue;
+            }
+            if area.vpn_range.get_start() < start_vpn {
+                let rest = area.split_off(start_vpn);
+                self.areas.push(area);
+                area = rest;
+            }
+            if end_vpn < area.vpn_range.get_end() {
+                let rest = area.split_off(end_vpn);
+                self.areas.push(rest);
+            }
+            let (area_start, area_end) = (area.vpn_range.get_start(), area.vpn_range.get_end());
+            area.sync(&mut self.page_table, area_start, area_end);
+            area.unmap(&mut self.page_table);
+        }
+        true
+    }
+    pub fn translate(&self, vpn: VirtPageNum) -> Option<PageTableEntry> {
+        self.page_table.translate(vpn)
+    }
+    /// 回收应用地址空间中所有逻辑段占用的物理页帧，页表本身的页帧则等到MemorySet被drop时再回收
+    pub fn recycle_data_pages(&mut self) {
+        self.sync_all();
+        self.areas.clear();
+    }
+    /// 处理用户程序访问`vpn`时的缺页异常，能够处理时返回true，此时用户程序可以重新执行这条访存指令：
+    /// - Lazy逻辑段中还没有分配的页面，现在分配一个全0的物理页帧；File逻辑段则再从文件中读入页面的内容；
+    /// - 写时复制：fork之后父子进程共享可写页面的物理页帧，页表项中的W位被去掉，
+    ///   第一次写入时在这里为写入的一方准备好独占的物理页帧。
+    /// 地址不在任何逻辑段中，或者逻辑段的权限不允许这样访问时返回false，物理内存不足时返回Err
+    pub fn handle_page_fault(&mut self, vpn: VirtPageNum, access: Access) -> Result<bool, OutOfMemory> {
+        let idx = match self.areas.iter().position(|area| area.contains(vpn)) {
+            Some(idx) => idx,
+            None => return Ok(false),
+        };
+        let area = &self.areas[idx];
+        let required = match access {
+            Access::Read => MapPermission::R,
+            Access::Write => MapPermission::W,
+            Access::Exec => MapPermission::X,
+        };
+        if area.map_type == MapType::Identical
+            || !area.map_perm.contains(MapPermission::U)
+            || !area.map_perm.contains(required)
+        {
+            return Ok(false);
+        }
+        let is_write = access == Access::Write;
+        match self.page_table.translate(vpn) {
+            Some(pte) if pte.is_valid() => {
+                if !is_write || pte.writable() {
+                    // 已经被处理过了
+                    return Ok(true);
+                }
+            }
+            _ => {
+                if !area.swapped.contains_key(&vpn)
+                    && area.map_type != MapType::Lazy
+                    && area.map_type != MapType::File
+                {
+                    return Ok(false);
+                }
+                let frame = self.alloc_frame()?;
+                self.areas[idx].map_one_with(&mut self.page_table, vpn, frame)?;
+                return Ok(true);
+            }
+        }
+        let pte_flags = PTEFlags::from_bits(self.areas[idx].map_perm.bits).unwrap();
+        match self.areas[idx].data_frames.get(&vpn) {
+            None => return Ok(false),
+            Some(frame) if Arc::strong_count(frame) == 1 => {
+                // 其他进程都已经不再使用这个物理页帧，直接恢复写权限
+                self.page_table.remap(vpn, frame.ppn, pte_flags);
+                return Ok(true);
+            }
+            _ => {}
+        }
+        // 共享的页帧不会被换出，分配时不会影响到它
+        let new_frame = self.alloc_frame()?;
+        let area = &mut self.areas[idx];
+        new_frame
+            .ppn
+            .get_bytes_array()
+            .copy_from_slice(area.data_frames[&vpn].ppn.get_bytes_array());
+        self.page_table.remap(vpn, new_frame.ppn, pte_flags);
+        area.data_frames.insert(vpn, Arc::new(new_frame));
+        Ok(true)
+    }
+    /// 为这个地址空间分配一个物理页帧，物理内存不足时先换出一个页面，没有页面可以换出时返回Err
+    fn alloc_frame(&mut self) -> Result<FrameTracker, OutOfMemory> {
+        loop {
+            if let Some(frame) = frame_alloc() {
+                return Ok(frame);
+            }
+            if !self.swap_out_one() {
+                return Err(OutOfMemory);
+            }
+        }
+    }
+    /// 用改进的Clock算法选出一个页面换出，没有可以换出的页面时返回false。
+    /// 只在当前地址空间中挑选（局部置换），被多个进程共享的页帧和内核正在访问的页面不会被换出。
+    /// 按(A, 是否需要写盘)分类：MAP_SHARED文件映射中没有被写过的页面直接丢弃，之后再从文件读入，
+    /// 其余页面换出时都要写入交换区或者写回文件。程序自己的代码页面同样可以换出，取指时的缺页异常会把它换回来
+    fn swap_out_one(&mut self) -> bool {
+        let swap_full = swap_full();
+        let mut candidates: Vec<(VirtPageNum, usize)> = Vec::new();
+        for (idx, area) in self.areas.iter().enumerate() {
+            if area.map_type == MapType::Identical || !area.map_perm.contains(MapPermission::U) {
+                continue;
+            }
+            // 交换区满了之后只能换出可以写回文件的页面
+            if swap_full && !area.is_shared_file() {
+                continue;
+            }
+            for (vpn, frame) in area.data_frames.iter() {
+                if Arc::strong_count(frame) == 1 && !self.is_pinned(*vpn) {
+                    candidates.push((*vpn, idx));
+                }
+            }
+        }
+        if candidates.is_empty() {
+            return false;
+        }
+        candidates.sort_by_key(|(vpn, _)| *vpn);
+        let start = candidates
+            .iter()
+            .position(|(vpn, _)| *vpn >= self.clock_hand)
+            .unwrap_or(0);
+        // 第一圈找A = 0并且不需要写盘的页面，什么都不修改；
+        // 第二圈找A = 0的页面，同时清除经过的页面的A位；两圈都没有找到时再重复一次，这时一定能找到
+        for round in 0..4 {
+            for i in 0..candidates.len() {
+                let (vpn, idx) = candidates[(start + i) % candidates.len()];
+                let pte = self.page_table.translate(vpn).unwrap();
+                let needs_write = !self.areas[idx].is_shared_file() || pte.dirty();
+                if !pte.accessed() && (round % 2 == 1 || !needs_write) {
+                    self.clock_hand = VirtPageNum(vpn.0 + 1);
+                    self.evict(idx, vpn);
+                    return true;
+                }
+                if round % 2 == 1 {
+                    self.page_table
+                        .remap(vpn, pte.ppn(), pte.flags() - PTEFlags::A);
+                }
+            }
+        }
+        false
+    }
+    /// 把第idx个逻辑段中的vpn换出：MAP_SHARED文件映射写回文件，其余页面写入交换区
+    fn evict(&mut self, idx: usize, vpn: VirtPageNum) {
+        let area = &mut self.areas[idx];
+        if area.is_shared_file() {
+            area.sync(&mut self.page_table, vpn, VirtPageNum(vpn.0 + 1));
+            area.data_frames.remove(&vpn);
+        } else {
+            let frame = area.data_frames.remove(&vpn).unwrap();
+            let slot = swap_out(frame.ppn).unwrap();
+            area.swapped.insert(vpn, Arc::new(slot));
+        }
+        self.page_table.unmap(vpn);
+    }
+    fn is_pinned(&self, vpn: VirtPageNum) -> bool {
+        self.pinned.get_start() <= vpn && vpn < self.pinned.get_end()
+    }
+    /// 页面都准备好之后解除固定。置换只在进程自己的地址空间中进行，
+    /// 内核随后拷贝数据时不会再处理这个进程的缺页，这些页面不会被换出
+    fn unpin(&mut self) {
+        self.pinned = VPNRange::new(VirtPageNum(0), VirtPageNum(0));
+    }
+    /// 内核通过物理地址直接访问用户内存，不会触发缺页异常，所以在访问[start, start + len)之前，
+    /// 要先替用户程序处理好其中的缺页：分配Lazy页面，写入时还要把写时复制页面复制出来，
+    /// 否则会改到其他进程的数据。其中有用户程序不能这样访问的页面时返回Err(Fault)，
+    /// 物理内存不足时返回Err(OutOfMemory)，这两种情况下内核都不能访问这段内存
+    fn prepare_user_access(&mut self, start: usize, len: usize, access: Access) -> Result<(), UserAccessError> {
+        if len == 0 {
+            return Ok(());
+        }
+        // VirtAddr只保留低39位，超出用户地址空间的地址会被截断成其他页面，要先排除掉
+        let end = match start.checked_add(len) {
+            Some(end) if end <= USER_SPACE_END => end,
+            _ => return Err(UserAccessError::Fault),
+        };
+        let start_vpn = VirtAddr::from(start).floor();
+        let end_vpn = VirtAddr::from(end).ceil();
+        // 处理后面的页面时可能需要换出页面，不能换出前面已经处理好的
+        self.pinned = VPNRange::new(start_vpn, end_vpn);
+        let result = VPNRange::new(start_vpn, end_vpn)
+            .into_iter()
+            .try_for_each(|vpn| match self.handle_page_fault(vpn, access)? {
+                true => Ok(()),
+                false => Err(UserAccessError::Fault),
+            });
+        self.unpin();
+        result
+    }
+    /// 内核即将读取用户内存中从start开始、以'\0'结尾的字符串
+    pub fn prepare_user_str(&mut self, start: usize) -> Result<(), UserAccessError> {
+        let result = self.fault_in_str(start);
+        self.unpin();
+        result
+    }
+    fn fault_in_str(&mut self, start: usize) -> Result<(), UserAccessError> {
+        let start_vpn = VirtAddr::from(start).floor();
+        let mut va = start;
+        loop {
+            if va >= USER_SPACE_END {
+                return Err(UserAccessError::Fault);
+            }
+            let vpn = VirtAddr::from(va).floor();
+            self.pinned = VPNRange::new(start_vpn, VirtPageNum(vpn.0 + 1));
+            if !self.handle_page_fault(vpn, Access::Read)? {
+                return Err(UserAccessError::Fault);
+            }
+            let ppn = self.page_table.translate(vpn).unwrap().ppn();
+            let offset = VirtAddr::from(va).page_offset();
+            if ppn.get_bytes_array()[offset..].contains(&0) {
+                return Ok(());
+            }
+            va = VirtAddr::from(VirtPageNum(vpn.0 + 1)).0;
+        }
+    }
+    /// 内核即将读取用户内存[start, start + len)
+    pub fn prepare_user_read(&mut self, start: usize, len: usize) -> Result<(), UserAccessError> {
+        self.prepare_user_access(start, len, Access::Read)
+    }
+    /// 内核即将代替用户程序写入[start, start + len)
+    pub fn prepare_user_write(&mut self, start: usize, len: usize) -> Result<(), UserAccessError> {
+        self.prepare_user_access(start, len, Access::Write)
+    }
+    /// 用户程序占用的物理页帧数，OOM killer据此挑选进程
+    pub fn resident_pages(&self) -> usize {
+        self.areas
+            .iter()
+            .filter(|area| area.map_perm.contains(MapPermission::U))
+            .map(|area| area.data_frames.len())
+            .sum()
     }
-    pub fn from_elf(elf_data: &[u8]) -> (Self, usize, usize);
 }
 
 impl MapArea{
@@ -68,21 +509,144 @@ impl MapArea{
             data_frames: BTreeMap::new(),
             map_type,
             map_perm,
+            file: None,
+            swapped: BTreeMap::new(),
         }
     } 
-    pub fn map(&mut self, page_table: &mut PageTable){
+    /// 复制另一个逻辑段的元数据（不包括物理页帧），用于fork
+    pub fn from_another(another: &MapArea) -> Self {
+        Self {
+            vpn_range: VPNRange::new(another.vpn_range.get_start(), another.vpn_range.get_end()),
+            data_frames: BTreeMap::new(),
+            map_type: another.map_type,
+            map_perm: another.map_perm,
+            file: another.file.clone(),
+            swapped: BTreeMap::new(),
+        }
+    }
+    pub fn contains(&self, vpn: VirtPageNum) -> bool {
+        self.vpn_range.get_start() <= vpn && vpn < self.vpn_range.get_end()
+    }
+    /// 和[start_vpn, end_vpn)至少有一个公共页面
+    pub fn overlaps(&self, start_vpn: VirtPageNum, end_vpn: VirtPageNum) -> bool {
+        self.vpn_range.get_start().max(start_vpn) < self.vpn_range.get_end().min(end_vpn)
+    }
+    /// 从vpn处把逻辑段一分为二，自身保留[start, vpn)，返回[vpn, end)，物理页帧随页面一起划分
+    pub fn split_off(&mut self, vpn: VirtPageNum) -> MapArea {
+        let start = self.vpn_range.get_start();
+        let end = self.vpn_range.get_end();
+        self.vpn_range = VPNRange::new(start, vpn);
+        Self {
+            vpn_range: VPNRange::new(vpn, end),
+            data_frames: self.data_frames.split_off(&vpn),
+            swapped: self.swapped.split_off(&vpn),
+            map_type: self.map_type,
+            map_perm: self.map_perm,
+            file: self.file.as_ref().map(|file| FileMapping {
+                offset: file.offset + (vpn.0 - start.0) * PAGE_SIZE,
+                ..file.clone()
+            }),
+        }
+    }
+    pub fn map(&mut self, page_table: &mut PageTable) -> Result<(), OutOfMemory> {
+        if self.map_type == MapType::Lazy || self.map_type == MapType::File {
+            return Ok(());
+        }
+        if self.map_type == MapType::Identical {
+            // 恒等映射尽量使用大页，省下中间页表占用的物理页帧
+            let pte_flags = PTEFlags::from_bits(self.map_perm.bits).unwrap();
+            let mut vpn = self.vpn_range.get_start();
+            while vpn < self.vpn_range.get_end() {
+                if let Some(size) = self.huge_page_at(vpn) {
+                    page_table.map_huge(vpn, PhysPageNum(vpn.0), pte_flags, size)?;
+                    vpn = VirtPageNum(vpn.0 + size.pages());
+                } else {
+                    self.map_one(page_table, vpn)?;
+                    vpn.step();
+                }
+            }
+            return Ok(());
+        }
         for vpn in self.vpn_range {
-            self.map_one(page_table, vpn);
+            self.map_one(page_table, vpn)?;
         }
+        Ok(())
     }
     pub fn unmap(&mut self, page_table: &mut PageTable){
-        for von in self.vpn_range {
+        if self.map_type == MapType::Identical {
+            let mut vpn = self.vpn_range.get_start();
+            while vpn < self.vpn_range.get_end() {
+                if let Some(size) = self.huge_page_at(vpn) {
+                    page_table.unmap_huge(vpn, size);
+                    vpn = VirtPageNum(vpn.0 + size.pages());
+                } else {
+                    self.unmap_one(page_table, vpn);
+                    vpn.step();
+                }
+            }
+            return;
+        }
+        for vpn in self.vpn_range {
+            self.unmap_one(page_table, vpn);
+        }
+    }
+    /// 恒等映射中从vpn开始能放下的最大的大页
+    fn huge_page_at(&self, vpn: VirtPageNum) -> Option<HugePage> {
+        [HugePage::Giga, HugePage::Mega].into_iter().find(|size| {
+            vpn.0 % size.pages() == 0 && vpn.0 + size.pages() <= self.vpn_range.get_end().0
+        })
+    }
+    pub fn shrink_to(&mut self, page_table: &mut PageTable, new_end: VirtPageNum) {
+        for vpn in VPNRange::new(new_end, self.vpn_range.get_end()) {
             self.unmap_one(page_table, vpn);
         }
+        self.vpn_range = VPNRange::new(self.vpn_range.get_start(), new_end);
+    }
+    pub fn append_to(&mut self, page_table: &mut PageTable, new_end: VirtPageNum) -> Result<(), OutOfMemory> {
+        // Lazy逻辑段只需要扩大范围，新页面等到访问时再分配
+        if self.map_type != MapType::Lazy && self.map_type != MapType::File {
+            for vpn in VPNRange::new(self.vpn_range.get_end(), new_end) {
+                self.map_one(page_table, vpn)?;
+                // 逐页扩大范围，中途失败时已经映射的页面也属于这个逻辑段
+                self.vpn_range = VPNRange::new(self.vpn_range.get_start(), VirtPageNum(vpn.0 + 1));
+            }
+        }
+        self.vpn_range = VPNRange::new(self.vpn_range.get_start(), new_end);
+        Ok(())
     }
 
+    /// 文件映射中vpn这一页在文件中的偏移
+    fn file_offset(&self, vpn: VirtPageNum) -> usize {
+        self.file.as_ref().unwrap().offset + (vpn.0 - self.vpn_range.get_start().0) * PAGE_SIZE
+    }
+    /// 把[start_vpn, end_vpn)中被写过的页面写回文件，只对MAP_SHARED文件映射有效。
+    /// 文件不会因此变长，超出文件末尾的部分被丢弃
+    pub fn sync(&self, page_table: &mut PageTable, start_vpn: VirtPageNum, end_vpn: VirtPageNum) {
+        let file = match &self.file {
+            Some(file) if file.shared => file,
+            _ => return,
+        };
+        if start_vpn >= end_vpn {
+            return;
+        }
+        let size = file.inode.size();
+        for (vpn, frame) in self.data_frames.range(start_vpn..end_vpn) {
+            let pte = page_table.translate(*vpn).unwrap();
+            if !pte.dirty() {
+                continue;
+            }
+            let offset = self.file_offset(*vpn);
+            if offset < size {
+                let len = PAGE_SIZE.min(size - offset);
+                file.inode
+                    .write_at(offset, &frame.ppn.get_bytes_array()[..len]);
+            }
+            // 清除D位，之后再写入时硬件会重新置上
+            page_table.remap(*vpn, pte.ppn(), pte.flags() - PTEFlags::D);
+        }
+    }
     pub fn copy_data(&mut self, page_table: &mut PageTable, data: &[u8]){
-        assert_eq!(self.map_type, MapTpye::Framed);
+        assert_eq!(self.map_type, MapType::Framed);
         let mut start: usize = 0;
         let mut current_vpn = self.vpn_range.get_start();
         let len = data.len();
@@ -101,25 +665,54 @@ impl MapArea{
             current_vpn.step();
         }
     }
-    pub fn map_one(&mut self, page_table: &mut PageTable, vpn: VirtPageNum){
-        let ppn: PhysPageNum;
-        match self.map_type {
-            MapTpye::Identical => {
-                ppn = PhysPageNum(vpn.0);
-            }
-            MapType::Framed => {
-                let frame = frame_alloc().unwrap();
-                ppn = frame.ppn;
-                self.data_frames.insert(vpn, frame);
-            }
+    pub fn map_one(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) -> Result<(), OutOfMemory> {
+        if self.map_type == MapType::Identical {
+            let pte_flags = PTEFlags::from_bits(self.map_perm.bits).unwrap();
+            page_table.map(vpn, PhysPageNum(vpn.0), pte_flags)
+        } else {
+            let frame = frame_alloc().ok_or(OutOfMemory)?;
+            self.map_one_with(page_table, vpn, frame)
         }
+    }
+    /// 把vpn映射到已经分配好的物理页帧：换出过的页面从交换区读回，File逻辑段从文件读入，其他页面保持为0
+    pub fn map_one_with(&mut self, page_table: &mut PageTable, vpn: VirtPageNum, frame: FrameTracker) -> Result<(), OutOfMemory> {
+        // 先建立映射，分配页表所需的物理页帧失败时页面仍然留在交换区中
         let pte_flags = PTEFlags::from_bits(self.map_perm.bits).unwrap();
-        page_table.map(vpn, ppn, pte_flags);
+        page_table.map(vpn, frame.ppn, pte_flags)?;
+        if let Some(slot) = self.swapped.remove(&vpn) {
+            // 槽位被fork出的进程共享时，每个进程各自读回一份，最后一个读回的进程释放槽位
+            slot.read(frame.ppn);
+        } else if self.map_type == MapType::File {
+            // 超出文件末尾的部分保持为0
+            self.file
+                .as_ref()
+                .unwrap()
+                .inode
+                .read_at(self.file_offset(vpn), frame.ppn.get_bytes_array());
+        }
+        if self.map_perm.contains(MapPermission::X) {
+            // 页面内容是内核用普通的写操作填入的，取指之前要同步指令缓存
+            unsafe {
+                asm!("fence.i");
+            }
+        }
+        self.data_frames.insert(vpn, Arc::new(frame));
+        Ok(())
+    }
+    fn is_shared_file(&self) -> bool {
+        matches!(&self.file, Some(file) if file.shared)
     }
     pub fn unmap_one(&mut self, page_table: &mut PageTable, vpn: VirtPageNum){
+        if self.swapped.remove(&vpn).is_some() {
+            // 页面在交换区中，页表项已经无效
+            return;
+        }
         match self.map_type {
-            MapType::Framed => {
-                self.data_frames.remove(&vpn);
+            MapType::Framed | MapType::Lazy | MapType::File => {
+                if self.data_frames.remove(&vpn).is_none() {
+                    // 从未被访问过，或者映射时物理内存不足，页表项本来就无效
+                    return;
+                }
             }
             _ => {}
         }
@@ -142,60 +735,105 @@ extern "C" {
 }
 
 impl MemorySet{
+    /// 内核地址空间在启动时建立，这时物理内存不可能不足
     pub fn new_kernel() -> Self {
-        let mut memory_set = Self::new_bare();
+        // 内核地址空间使用ASID 0
+        let mut memory_set = Self::new_with_asid(AsidHandle(0)).unwrap();
 
-        memory_set.map_trampoline();
+        memory_set.map_trampoline().unwrap();
 
-        println!(".text [{:#x}, {:#x}]", stext as usize, etext as usize);
-        println!(".rodata [{:#x}, {:#x}]", srodata as usize, erodata as usize);
-        println!(".data [{:#x}, {:#x}]", sdata as usize, edata as usize);
-        println!(".bss [{:#x}, {:#x}]", sbss_with_stack as usize, ebss as usize);
+        println!(".text [{:#x}, {:#x})", stext as usize, etext as usize);
+        println!(".rodata [{:#x}, {:#x})", srodata as usize, erodata as usize);
+        println!(".data [{:#x}, {:#x})", sdata as usize, edata as usize);
+        println!(".bss [{:#x}, {:#x})", sbss_with_stack as usize, ebss as usize);
+        println!("mapping .text section");
         memory_set.push(MapArea::new(
             (stext as usize).into(),
             (etext as usize).into(),
-            MapType::Indentical,
+            MapType::Identical,
             MapPermission::R | MapPermission::X,
-        ), None);
+        ), None).unwrap();
         println!("mapping .rodata section");
+        memory_set.push(MapArea::new(
+            (srodata as usize).into(),
+            (erodata as usize).into(),
+            MapType::Identical,
+            MapPermission::R,
+        ), None).unwrap();
+        println!("mapping .data section");
         memory_set.push(MapArea::new(
             (sdata as usize).into(),
             (edata as usize).into(),
-            MapType::Indentical,
+            MapType::Identical,
             MapPermission::R | MapPermission::W,
-        ), None);
+        ), None).unwrap();
         println!("mapping .bss section");
         memory_set.push(MapArea::new(
             (sbss_with_stack as usize).into(),
             (ebss as usize).into(),
-            MapType::Indentical,
+            MapType::Identical,
             MapPermission::R | MapPermission::W,
-        ));
+        ), None).unwrap();
         println!("mapping physical memory");
         memory_set.push(MapArea::new(
             (ekernel as usize).into(),
             MEMORY_END.into(),
-            MapType::Indentical,
+            MapType::Identical,
             MapPermission::R | MapPermission::W,
-        ), None);
+        ), None).unwrap();
+        println!("mapping memory-mapped registers");
+        for pair in MMIO {
+            memory_set.push(MapArea::new(
+                (*pair).0.into(),
+                ((*pair).0 + (*pair).1).into(),
+                MapType::Identical,
+                MapPermission::R | MapPermission::W,
+            ), None).unwrap();
+        }
         memory_set
     }
 }
 
-impl MemorySet {
-    pub fn from_elf(elf_data: &[u8]) -> (Self, usize, usize) {
-        let mut memory_set = Self::new_bare();
+/// 由ELF文件创建地址空间失败的原因
+#[derive(Debug)]
+pub enum ExecError {
+    /// 不是合法的ELF文件，或者要加载到用户地址空间之外
+    BadElf,
+    /// 物理内存不足
+    OutOfMemory,
+}
 
-        memory_set.map_trampoline();
-        let elf = xmas_elf::ElfFile::new(elf_data).unwrap();
+impl From<OutOfMemory> for ExecError {
+    fn from(_: OutOfMemory) -> Self {
+        ExecError::OutOfMemory
+    }
+}
+
+impl MemorySet {
+    /// 解析ELF文件创建用户地址空间，返回地址空间、用户栈顶和入口地址
+    pub fn from_elf(elf_data: &[u8]) -> Result<(Self, usize, usize), ExecError> {
+        let elf = xmas_elf::ElfFile::new(elf_data).map_err(|_| ExecError::BadElf)?;
         let elf_header = elf.header;
         let magic = elf_header.pt1.magic;
-        assert_eq!(magic, [0x7f, 0x45, 0x4c, 0x46], "invalid elf!");
+        if magic != [0x7f, 0x45, 0x4c, 0x46] {
+            return Err(ExecError::BadElf);
+        }
+        let mut memory_set = Self::new_bare()?;
+        memory_set.map_trampoline()?;
         let ph_count = elf_header.pt2.ph_count();
         let mut max_end_vpn = VirtPageNum(0);
-        for i in 0.ph_count {
-            let ph = elf.program_header(i).unwrap();
-            if ph.get_type().unwrap() == xmas_elf::program::Type::Load{
+        for i in 0..ph_count {
+            let ph = elf.program_header(i).map_err(|_| ExecError::BadElf)?;
+            if matches!(ph.get_type(), Ok(xmas_elf::program::Type::Load)) {
+                // 段必须完整地落在用户地址空间中，文件中的内容也必须都在ELF文件里
+                let mem_end = ph.virtual_addr().checked_add(ph.mem_size());
+                let file_end = ph.offset().checked_add(ph.file_size());
+                if !matches!(mem_end, Some(end) if end as usize <= USER_SPACE_END)
+                    || !matches!(file_end, Some(end) if end as usize <= elf_data.len())
+                    || ph.file_size() > ph.mem_size()
+                {
+                    return Err(ExecError::BadElf);
+                }
                 let start_va: VirtAddr = (ph.virtual_addr() as usize).into();
                 let end_va: VirtAddr = ((ph.virtual_addr() + ph.mem_size()) as usize).into();
                 let mut map_perm = MapPermission::U;
@@ -203,46 +841,109 @@ impl MemorySet {
                 if ph_flags.is_read() {map_perm |= MapPermission:: R; }
                 if ph_flags.is_write() {map_perm |= MapPermission::W; }
                 if ph_flags.is_execute() { map_perm |= MapPermission::X; }
-                let map_area = MapArea::new(
-                    start_va,
-                    end_va,
-                    MapType::Framed,
-                    map_perm,
-                );
-                max_end_vpn = map_area.vpn_range.get_end();
-                memory_set.push(
-                    map_area,
-                    Some(&elf.input[ph.offset() as usize..(ph.offset() + ph.file_size()) as usize])
-                );
+                max_end_vpn = end_va.ceil();
+                // 有文件内容的部分立即分配并拷贝，之后只需要清零的部分（.bss）等到访问时再分配
+                let mut lazy_start_vpn = start_va.floor();
+                if ph.file_size() > 0 {
+                    let data_end_va: VirtAddr =
+                        ((ph.virtual_addr() + ph.file_size()) as usize).into();
+                    lazy_start_vpn = data_end_va.ceil();
+                    memory_set.push(
+                        MapArea::new(start_va, data_end_va, MapType::Framed, map_perm),
+                        Some(&elf.input[ph.offset() as usize..(ph.offset() + ph.file_size()) as usize])
+                    )?;
+                }
+                if lazy_start_vpn < max_end_vpn {
+                    memory_set.push(
+                        MapArea::new(lazy_start_vpn.into(), end_va, MapType::Lazy, map_perm),
+                        None,
+                    )?;
+                }
             }
         } 
-        ket nax_end_va: VirtAddr = max_end_vpn.into();
+        let max_end_va: VirtAddr = max_end_vpn.into();
         let mut user_stack_bottom: usize = max_end_va.into();
         // guard page
         user_stack_bottom += PAGE_SIZE;
         let user_stack_top = user_stack_bottom + USER_STACK_SIZE;
+        // 用户栈按需分配，程序实际用到多深就占用多少物理页帧
         memory_set.push(MapArea::new(
             user_stack_bottom.into(),
             user_stack_top.into(),
-            MapType::Framed,
+            MapType::Lazy,
+            MapPermission::R | MapPermission::W | MapPermission::U,
+        ), None)?;
+        // 堆紧接在用户栈之上，一开始是空的，由sbrk调整其大小
+        memory_set.push(MapArea::new(
+            user_stack_top.into(),
+            user_stack_top.into(),
+            MapType::Lazy,
             MapPermission::R | MapPermission::W | MapPermission::U,
-        ), None);
+        ), None)?;
         // map TrapContext
         memory_set.push(MapArea::new(
             TRAP_CONTEXT.into(),
             TRAMPOLINE.into(),
             MapType::Framed,
             MapPermission:: R | MapPermission::W,
-        ), None);
-        (memory_set, user_stack_top, elf.header.pt2.entry_point() as usize)
+        ), None)?;
+        Ok((memory_set, user_stack_top, elf.header.pt2.entry_point() as usize))
     }
 
+    /// fork时复制一个相同的用户地址空间。用户可以访问的逻辑段不拷贝数据，父子进程共享物理页帧，
+    /// 可写的页面在双方的页表中都变为只读，等到第一次写入时再复制（见handle_page_fault）；
+    /// Lazy逻辑段中父进程还没有访问过的页面，子进程同样在访问时才分配；
+    /// trap上下文等内核使用的逻辑段每个进程必须独占，仍然立即拷贝
+    /// 物理内存不足时返回Err，已经变为只读的页面在父进程写入时由handle_page_fault恢复
+    pub fn from_existed_user(user_space: &mut MemorySet) -> Result<MemorySet, OutOfMemory> {
+        let mut memory_set = Self::new_bare()?;
+        memory_set.map_trampoline()?;
+        for area in user_space.areas.iter() {
+            let mut new_area = MapArea::from_another(area);
+            if area.map_type == MapType::Identical || !area.map_perm.contains(MapPermission::U) {
+                memory_set.push(new_area, None)?;
+                for vpn in area.vpn_range {
+                    let src_ppn = user_space.translate(vpn).unwrap().ppn();
+                    let dst_ppn = memory_set.translate(vpn).unwrap().ppn();
+                    dst_ppn
+                        .get_bytes_array()
+                        .copy_from_slice(src_ppn.get_bytes_array());
+                }
+                continue;
+            }
+            let mut pte_flags = PTEFlags::from_bits(area.map_perm.bits).unwrap();
+            // 换出的页面在交换区中的槽位也由父子进程共享，各自在访问时读回
+            new_area.swapped = area.swapped.clone();
+            // MAP_SHARED文件映射中已经在内存中的页面由父子进程直接共享，不需要写时复制；
+            // 还没有读入的页面各自在访问时从文件读入，不再共享
+            if area.is_shared_file() {
+                for (vpn, frame) in area.data_frames.iter() {
+                    memory_set.page_table.map(*vpn, frame.ppn, pte_flags)?;
+                    new_area.data_frames.insert(*vpn, Arc::clone(frame));
+                }
+                memory_set.areas.push(new_area);
+                continue;
+            }
+            pte_flags.remove(PTEFlags::W);
+            for (vpn, frame) in area.data_frames.iter() {
+                memory_set.page_table.map(*vpn, frame.ppn, pte_flags)?;
+                user_space.page_table.remap(*vpn, frame.ppn, pte_flags);
+                new_area.data_frames.insert(*vpn, Arc::clone(frame));
+            }
+            memory_set.areas.push(new_area);
+        }
+        Ok(memory_set)
+    }
 }
 
 lazy_static!{
     pub static ref KERNEL_SPACE: Arc<UPSafeCell<MemorySet>> = Arc::new(unsafe {
         UPSafeCell::new(MemorySet::new_kernel())
-    )};
+    });
+}
+
+pub fn kernel_token() -> usize {
+    KERNEL_SPACE.exclusive_access().token()
 }
 
 impl MemorySet {
@@ -250,15 +951,16 @@ impl MemorySet {
         let satp = self.page_table.token();
         unsafe {
             satp::write(satp);
-            asm!("sfence.vma" :::: "volatile");
+            asm!("sfence.vma");
         }
     }
 }
 
 pub fn remap_test() {
-    let mut kernel_space = KERNEL_SPACE.lock();
+    let kernel_space = KERNEL_SPACE.exclusive_access();
     let mid_text: VirtAddr = ((stext as usize + etext as usize) / 2).into();
     let mid_rodata: VirtAddr = ((srodata as usize + erodata as usize) / 2).into();
+    let mid_data: VirtAddr = ((sdata as usize + edata as usize) / 2).into();
     assert_eq!(
         kernel_space.page_table.translate(mid_text.floor()).unwrap().writable(),
         false
@@ -267,15 +969,25 @@ pub fn remap_test() {
         kernel_space.page_table.translate(mid_rodata.floor()).unwrap().writable(),
         false,
     );
+    assert_eq!(
+        kernel_space.page_table.translate(mid_data.floor()).unwrap().executable(),
+        false,
+    );
+    // 物理内存的恒等映射用到了大页，translate仍然要给出每一页各自的物理页号
+    let last_page: VirtAddr = (MEMORY_END - PAGE_SIZE).into();
+    assert_eq!(
+        kernel_space.page_table.translate(last_page.floor()).unwrap().ppn().0,
+        last_page.floor().0,
+    );
     println!("remap_test passes!");
 }
 
 impl MemorySet{
-    fn map_trampoline(&mut self){
+    fn map_trampoline(&mut self) -> Result<(), OutOfMemory> {
         self.page_table.map(
             VirtAddr::from(TRAMPOLINE).into(),
             PhysAddr::from(strampoline as usize).into(),
             PTEFlags::R | PTEFlags::X,
-        );
+        )
     }
-}
\ No newline at end of file
+}
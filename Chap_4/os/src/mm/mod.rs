@@ -1,7 +1,27 @@
-pub use memory_set::KERNEL_SPACE;
+mod address;
+mod asid;
+mod frame_allocator;
+mod heap_allocator;
+mod memory_set;
+mod page_table;
+mod swap;
+
+pub use address::{PhysAddr, PhysPageNum, StepByOne, VirtAddr, VirtPageNum};
+use address::VPNRange;
+pub use frame_allocator::{frame_alloc, frame_alloc_contiguous, FrameTracker, OutOfMemory};
+pub use memory_set::remap_test;
+pub use memory_set::{
+    kernel_token, Access, ExecError, MapPermission, MemorySet, UserAccessError, KERNEL_SPACE,
+};
+pub use page_table::{
+    translated_byte_buffer, translated_refmut, translated_str, PageTable, PageTableEntry,
+    UserBuffer,
+};
+use page_table::{HugePage, PTEFlags};
 
 pub fn init(){
     heap_allocator::init_heap();
     frame_allocator::init_frame_allocator();
     KERNEL_SPACE.exclusive_access().activate();
-}
\ No newline at end of file
+    asid::init_asid();
+}
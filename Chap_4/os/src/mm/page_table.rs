@@ -1,4 +1,12 @@
-use bitflag::*;
+use super::{
+    frame_alloc, FrameTracker, OutOfMemory, PhysAddr, PhysPageNum, StepByOne, VirtAddr, VirtPageNum,
+};
+use super::asid::ASID_SHIFT;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use bitflags::*;
+use core::arch::asm;
 
 bitflags! {
     pub struct PTEFlags: u8 {
@@ -20,9 +28,9 @@ pub struct PageTableEntry {
 }
 
 impl PageTableEntry {
-    pub fn new(pnn: PhysPageNum, flags: PTEFlags) -> Self {
+    pub fn new(ppn: PhysPageNum, flags: PTEFlags) -> Self {
         PageTableEntry {
-            bits: pnn.0 << 10 | flags.bits as usize,
+            bits: ppn.0 << 10 | flags.bits as usize,
         }
     }
     pub fn empty() -> Self {     // 生成全为0的页表项，暗示页表项的V标志位为0，因此其不合法
@@ -39,109 +47,303 @@ impl PageTableEntry {
     pub fn is_valid(&self) -> bool {
         (self.flags() & PTEFlags::V) != PTEFlags::empty()
     }
+    pub fn readable(&self) -> bool {
+        (self.flags() & PTEFlags::R) != PTEFlags::empty()
+    }
+    pub fn writable(&self) -> bool {
+        (self.flags() & PTEFlags::W) != PTEFlags::empty()
+    }
+    pub fn executable(&self) -> bool {
+        (self.flags() & PTEFlags::X) != PTEFlags::empty()
+    }
+    /// 用户态可以访问的页面
+    pub fn user(&self) -> bool {
+        (self.flags() & PTEFlags::U) != PTEFlags::empty()
+    }
+    /// 页面被访问过之后由硬件置上A位
+    pub fn accessed(&self) -> bool {
+        (self.flags() & PTEFlags::A) != PTEFlags::empty()
+    }
+    /// 页面被写过之后由硬件置上D位
+    pub fn dirty(&self) -> bool {
+        (self.flags() & PTEFlags::D) != PTEFlags::empty()
+    }
+    /// R、W、X中有任意一位时是叶子页表项，否则指向下一级页表
+    pub fn is_leaf(&self) -> bool {
+        (self.flags() & (PTEFlags::R | PTEFlags::W | PTEFlags::X)) != PTEFlags::empty()
+    }
+}
+
+/// SV39中的大页：二级页表中的叶子页表项映射2MiB，一级页表（根节点）中的叶子页表项映射1GiB
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum HugePage {
+    Mega,
+    Giga,
+}
+
+impl HugePage {
+    /// 大页包含的4KiB页面数
+    pub fn pages(self) -> usize {
+        1 << (9 * (2 - self.level()))
+    }
+    /// 叶子页表项所在的层级，0为根节点
+    fn level(self) -> usize {
+        match self {
+            HugePage::Mega => 1,
+            HugePage::Giga => 0,
+        }
+    }
 }
 
 pub struct PageTable{
     root_ppn: PhysPageNum,
     frames: Vec<FrameTracker>,
+    asid: usize,
 }
 
 impl PageTable {
-    pub fn new() -> Self {
-        let frame = frame_alloc().unwrap();
-        PageTable {
+    pub fn new(asid: usize) -> Result<Self, OutOfMemory> {
+        let frame = frame_alloc().ok_or(OutOfMemory)?;
+        Ok(PageTable {
             root_ppn: frame.ppn,
             frames: vec![frame],
-        }
+            asid,
+        })
     }
 }
 
 impl PageTable {
-    pub fn map(&mut self, vpn: VirtPageNum, ppn: PhysPageNum, flags: PTEFlags){
-        let pte = self.find_pte_create(vpn).unwrap();
+    /// 中间的页表节点需要新分配物理页帧，分配不到时返回Err
+    pub fn map(&mut self, vpn: VirtPageNum, ppn: PhysPageNum, flags: PTEFlags) -> Result<(), OutOfMemory> {
+        let pte = self.find_pte_create(vpn, 2)?;
         assert!(!pte.is_valid(), "vpn {:?} is mapped before mapping", vpn);
         *pte = PageTableEntry::new(ppn, flags | PTEFlags::V);
+        self.flush(vpn);
+        Ok(())
+    }
+    /// 用一个高层的叶子页表项映射一个大页，vpn和ppn都必须按大页的大小对齐
+    pub fn map_huge(
+        &mut self,
+        vpn: VirtPageNum,
+        ppn: PhysPageNum,
+        flags: PTEFlags,
+        size: HugePage,
+    ) -> Result<(), OutOfMemory> {
+        assert!(
+            vpn.0 % size.pages() == 0 && ppn.0 % size.pages() == 0,
+            "vpn {:?} or ppn {:?} is not aligned to a {:?} page", vpn, ppn, size
+        );
+        let pte = self.find_pte_create(vpn, size.level())?;
+        assert!(!pte.is_valid(), "vpn {:?} is mapped before mapping", vpn);
+        *pte = PageTableEntry::new(ppn, flags | PTEFlags::V);
+        self.flush(vpn);
+        Ok(())
+    }
+    /// 修改一个已经映射的页表项，写时复制时用来换上新的物理页帧或恢复写权限
+    pub fn remap(&mut self, vpn: VirtPageNum, ppn: PhysPageNum, flags: PTEFlags) {
+        let (pte, level) = self.find_pte(vpn).unwrap();
+        assert!(pte.is_valid(), "vpn {:?} is invalid before remapping", vpn);
+        assert_eq!(level, 2, "vpn {:?} is mapped by a huge page", vpn);
+        *pte = PageTableEntry::new(ppn, flags | PTEFlags::V);
+        self.flush(vpn);
     }
     pub fn unmap(&mut self, vpn: VirtPageNum){
-        let pte = self.find_pte(vpn).unwrap();
+        let (pte, level) = self.find_pte(vpn).unwrap();
+        assert!(pte.is_valid(), "vpn {:?} is invalid before unmapping", vpn);
+        assert_eq!(level, 2, "vpn {:?} is mapped by a huge page", vpn);
+        *pte = PageTableEntry::empty();
+        self.flush(vpn);
+    }
+    /// 解除map_huge建立的映射
+    pub fn unmap_huge(&mut self, vpn: VirtPageNum, size: HugePage) {
+        let (pte, level) = self.find_pte(vpn).unwrap();
         assert!(pte.is_valid(), "vpn {:?} is invalid before unmapping", vpn);
+        assert_eq!(level, size.level(), "vpn {:?} is not mapped by a {:?} page", vpn, size);
         *pte = PageTableEntry::empty();
+        self.flush(vpn);
     }
-    fn find_pte_create(&mut self, vpn: VirtPageNum) -> Option<&mut PageTabelEntry> {    // 在多级页表中找到一个虚拟页号对用的页表项的可变引用
+    fn find_pte_create(&mut self, vpn: VirtPageNum, level: usize) -> Result<&mut PageTableEntry, OutOfMemory> {    // 在多级页表中找到一个虚拟页号在第level级对应的页表项的可变引用
         let idxs = vpn.indexes();
         let mut ppn = self.root_ppn;     // 当前节点的物理页号
-        let mut result: Option<&mut PageTabelEntry> = None;
-        for i in 0..3 {
+        for i in 0..level {
             let pte = &mut ppn.get_pte_array()[idxs[i]];
-            if i == 2 {
-                result = Some(pte);
-                break;
-            }
+            assert!(!pte.is_leaf(), "vpn {:?} is mapped by a huge page", vpn);
             if !pte.is_valid() {                            // 如果发现有节点尚未创建，则会新建一个节点
-                let frame = frame_alloc().unwrap();
-                *pte = PageTabelEntry::new(frame.ppn, PTEFlags::V);
+                let frame = frame_alloc().ok_or(OutOfMemory)?;
+                *pte = PageTableEntry::new(frame.ppn, PTEFlags::V);
                 self.frames.push(frame);
             }
-            ppn = pte.ppn()'
+            ppn = pte.ppn();
         }
-        result
+        Ok(&mut ppn.get_pte_array()[idxs[level]])
     }
-    fn find_pte(&self, vpn: VirtPageNum) -> Option<&mut PageTableEntry> {
+    /// 找到映射vpn的页表项及其所在的层级，大页的叶子页表项在第0或第1级
+    fn find_pte(&self, vpn: VirtPageNum) -> Option<(&mut PageTableEntry, usize)> {
         let idxs = vpn.indexes();
         let mut ppn = self.root_ppn;
-        let mut result: Option<&mut PageTableEntry> = None;
-        for i in 0..3{
-            let pte = &mut ppn.get_pte_array()[idxs[i]];
-            if i == 2{
-                result = Some(pte);
-                break;
+        for (i, idx) in idxs.iter().enumerate() {
+            let pte = &mut ppn.get_pte_array()[*idx];
+            if i == 2 || pte.is_leaf() {
+                return Some((pte, i));
             }
             if !pte.is_valid(){
                 return None;
             }
-            pnn = pte.ppn()
+            ppn = pte.ppn();
         }
-        result
+        unreachable!()
     }
-    pub fn from_token(satp: usize) -> Self {
-        pub fn from_token(satp: usize) -> Self {
-            Self {
-                root_ppn: PhysPageNum::from(satp & ((1usize << 44) - 1)),
-                frames: Vec::new(),
-            }
+    pub fn from_token(satp: usize) -> Self {      // 临时创建一个专用来手动查页表的PageTable，它不持有任何物理页帧
+        Self {
+            root_ppn: PhysPageNum::from(satp & ((1usize << 44) - 1)),
+            frames: Vec::new(),
+            asid: (satp >> ASID_SHIFT) & 0xffff,
         }
-        pub fn translate(&self, vpn: VirtPageNum) -> Option<PageTabelEntry>{
-            self.find_pte(vpn)
-                .map(|pte| {pte.clone()})
+    }
+    /// 映射在大页中时，返回的页表项中的物理页号是vpn自己所对应的那一页
+    pub fn translate(&self, vpn: VirtPageNum) -> Option<PageTableEntry>{
+        self.find_pte(vpn).map(|(pte, level)| {
+            if level == 2 {
+                return pte.clone();
+            }
+            let offset = vpn.0 & ((1 << (9 * (2 - level))) - 1);
+            PageTableEntry::new(PhysPageNum(pte.ppn().0 + offset), pte.flags())
+        })
+    }
+    pub fn translate_va(&self, va: VirtAddr) -> Option<PhysAddr> {
+        self.translate(va.clone().floor()).map(|pte| {
+            let aligned_pa: PhysAddr = pte.ppn().into();
+            let offset = va.page_offset();
+            let aligned_pa_usize: usize = aligned_pa.into();
+            (aligned_pa_usize + offset).into()
+        })
+    }
+    /// satp的值：SV39模式、ASID和根节点的物理页号
+    pub fn token(&self) -> usize {
+        8usize << 60 | self.asid << ASID_SHIFT | self.root_ppn.0
+    }
+    /// 页表项被修改之后，TLB中可能还留有旧的表项，只刷新这个地址空间中vpn对应的那一项
+    fn flush(&self, vpn: VirtPageNum) {
+        let va: VirtAddr = vpn.into();
+        unsafe {
+            asm!("sfence.vma {}, {}", in(reg) va.0, in(reg) self.asid);
         }
     }
 }
 
-pub fn token(&self) -> usize {
-    8usize << 60 | self.root_ppn.0
+/// 用户程序可以访问的页面vpn所在的物理页号，页表项无效或者没有U位时返回None
+fn user_ppn(page_table: &PageTable, vpn: VirtPageNum) -> Option<PhysPageNum> {
+    page_table
+        .translate(vpn)
+        .filter(|pte| pte.is_valid() && pte.user())
+        .map(|pte| pte.ppn())
 }
 
+/// 把用户地址空间中的[ptr, ptr + len)按页拆成若干片，其中有用户不能访问的页面时返回None
 pub fn translated_byte_buffer(
     token: usize,
     ptr: *const u8,
     len: usize,
-) -> Vec<&'static [u8]> {
+) -> Option<Vec<&'static mut [u8]>> {
     let page_table = PageTable::from_token(token);
     let mut start = ptr as usize;
-    let end = start + len;
+    let end = start.checked_add(len)?;
     let mut v = Vec::new();
     while start < end {
         let start_va = VirtAddr::from(start);
         let mut vpn = start_va.floor();
-        let ppn = page_table
-            .translate(vpn)
-            .unwrap()
-            .ppn();
+        let ppn = user_ppn(&page_table, vpn)?;
         vpn.step();
-        let mut e end_va: VirtAddr = vpn.into();
+        let mut end_va: VirtAddr = vpn.into();
         end_va = end_va.min(VirtAddr::from(end));
-        v.push(&ppn.get_bytes_array()[start_va.page_offset()..end_va.page_offset()]);
+        if end_va.page_offset() == 0 {
+            v.push(&mut ppn.get_bytes_array()[start_va.page_offset()..]);
+        } else {
+            v.push(&mut ppn.get_bytes_array()[start_va.page_offset()..end_va.page_offset()]);
+        }
         start = end_va.into();
     }
-    v
-}
\ No newline at end of file
+    Some(v)
+}
+
+/// 从用户地址空间中读出一个以'\0'结尾的字符串，遇到用户不能访问的页面时返回None
+pub fn translated_str(token: usize, ptr: *const u8) -> Option<String> {
+    let page_table = PageTable::from_token(token);
+    let mut string = String::new();
+    let mut va = ptr as usize;
+    loop {
+        let addr = VirtAddr::from(va);
+        let ppn = user_ppn(&page_table, addr.floor())?;
+        let ch = ppn.get_bytes_array()[addr.page_offset()];
+        if ch == 0 {
+            break;
+        }
+        string.push(ch as char);
+        va += 1;
+    }
+    Some(string)
+}
+
+/// 获得用户地址空间中一个变量的可变引用，变量所在的页面用户不能访问时返回None
+pub fn translated_refmut<T>(token: usize, ptr: *mut T) -> Option<&'static mut T> {
+    let page_table = PageTable::from_token(token);
+    let va = VirtAddr::from(ptr as usize);
+    let ppn = user_ppn(&page_table, va.floor())?;
+    let aligned_pa: PhysAddr = ppn.into();
+    Some(PhysAddr::from(aligned_pa.0 + va.page_offset()).get_mut())
+}
+
+/// 用户地址空间中的一段缓冲区，它在物理上可能并不连续，因此按页拆成若干片
+pub struct UserBuffer {
+    pub buffers: Vec<&'static mut [u8]>,
+}
+
+impl UserBuffer {
+    pub fn new(buffers: Vec<&'static mut [u8]>) -> Self {
+        Self { buffers }
+    }
+    pub fn len(&self) -> usize {
+        let mut total: usize = 0;
+        for b in self.buffers.iter() {
+            total += b.len();
+        }
+        total
+    }
+}
+
+impl IntoIterator for UserBuffer {
+    type Item = *mut u8;
+    type IntoIter = UserBufferIterator;
+    fn into_iter(self) -> Self::IntoIter {
+        UserBufferIterator {
+            buffers: self.buffers,
+            current_buffer: 0,
+            current_idx: 0,
+        }
+    }
+}
+
+/// 逐字节遍历UserBuffer，屏蔽掉其在物理上分成若干片的细节
+pub struct UserBufferIterator {
+    buffers: Vec<&'static mut [u8]>,
+    current_buffer: usize,
+    current_idx: usize,
+}
+
+impl Iterator for UserBufferIterator {
+    type Item = *mut u8;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.current_buffer >= self.buffers.len() {
+            None
+        } else {
+            let r = &mut self.buffers[self.current_buffer][self.current_idx] as *mut _;
+            if self.current_idx + 1 == self.buffers[self.current_buffer].len() {
+                self.current_idx = 0;
+                self.current_buffer += 1;
+            } else {
+                self.current_idx += 1;
+            }
+            Some(r)
+        }
+    }
+}
@@ -0,0 +1,81 @@
+//! 交换区：块设备上紧接在文件系统之后的一段区域，划分为页面大小的槽位，存放被换出的用户页面
+use super::PhysPageNum;
+use crate::config::{PAGE_SIZE, SWAP_PAGES, SWAP_START_BLOCK};
+use crate::drivers::BLOCK_DEVICE;
+use crate::sync::UPSafeCell;
+use alloc::vec::Vec;
+use easy_fs::BLOCK_SZ;
+use lazy_static::*;
+
+const BLOCKS_PER_PAGE: usize = PAGE_SIZE / BLOCK_SZ;
+
+/// 和StackFrameAllocator一样，优先使用回收的槽位
+struct SwapAllocator {
+    current: usize,     // 从未使用过的槽位的起始编号
+    recycled: Vec<usize>,
+}
+
+impl SwapAllocator {
+    fn alloc(&mut self) -> Option<usize> {
+        if let Some(slot) = self.recycled.pop() {
+            Some(slot)
+        } else if self.current == SWAP_PAGES {
+            None
+        } else {
+            self.current += 1;
+            Some(self.current - 1)
+        }
+    }
+    fn dealloc(&mut self, slot: usize) {
+        if slot >= self.current || self.recycled.contains(&slot) {
+            panic!("Swap slot {} has not been allocated!", slot);
+        }
+        self.recycled.push(slot);
+    }
+    fn is_full(&self) -> bool {
+        self.recycled.is_empty() && self.current == SWAP_PAGES
+    }
+}
+
+lazy_static! {
+    static ref SWAP_ALLOCATOR: UPSafeCell<SwapAllocator> = unsafe {
+        UPSafeCell::new(SwapAllocator {
+            current: 0,
+            recycled: Vec::new(),
+        })
+    };
+}
+
+/// 交换区中的一个槽位，和FrameTracker一样在drop时自动回收
+pub struct SwapSlot(usize);
+
+impl SwapSlot {
+    fn first_block(&self) -> usize {
+        SWAP_START_BLOCK + self.0 * BLOCKS_PER_PAGE
+    }
+    /// 把槽位中保存的页面读到物理页帧ppn中
+    pub fn read(&self, ppn: PhysPageNum) {
+        for (i, block) in ppn.get_bytes_array().chunks_mut(BLOCK_SZ).enumerate() {
+            BLOCK_DEVICE.read_block(self.first_block() + i, block);
+        }
+    }
+}
+
+impl Drop for SwapSlot {
+    fn drop(&mut self) {
+        SWAP_ALLOCATOR.exclusive_access().dealloc(self.0);
+    }
+}
+
+/// 把物理页帧ppn中的页面写入一个新的槽位，交换区已满时返回None
+pub fn swap_out(ppn: PhysPageNum) -> Option<SwapSlot> {
+    let slot = SwapSlot(SWAP_ALLOCATOR.exclusive_access().alloc()?);
+    for (i, block) in ppn.get_bytes_array().chunks(BLOCK_SZ).enumerate() {
+        BLOCK_DEVICE.write_block(slot.first_block() + i, block);
+    }
+    Some(slot)
+}
+
+pub fn swap_full() -> bool {
+    SWAP_ALLOCATOR.exclusive_access().is_full()
+}
@@ -0,0 +1,48 @@
+//! 系统调用的错误码，与Linux保持一致，系统调用返回其相反数
+
+use crate::mm::UserAccessError;
+
+/// Operation not permitted
+pub const EPERM: isize = 1;
+/// No such file or directory
+pub const ENOENT: isize = 2;
+/// Exec format error
+pub const ENOEXEC: isize = 8;
+/// Bad file descriptor
+pub const EBADF: isize = 9;
+/// Out of memory
+pub const ENOMEM: isize = 12;
+/// Bad address
+pub const EFAULT: isize = 14;
+/// Device or resource busy
+pub const EBUSY: isize = 16;
+/// File exists
+pub const EEXIST: isize = 17;
+/// Not a directory
+pub const ENOTDIR: isize = 20;
+/// Is a directory
+pub const EISDIR: isize = 21;
+/// Invalid argument
+pub const EINVAL: isize = 22;
+/// File too large
+pub const EFBIG: isize = 27;
+/// No space left on device
+pub const ENOSPC: isize = 28;
+/// Illegal seek
+pub const ESPIPE: isize = 29;
+/// Broken pipe
+pub const EPIPE: isize = 32;
+/// Result too large
+pub const ERANGE: isize = 34;
+/// File name too long
+pub const ENAMETOOLONG: isize = 36;
+/// Directory not empty
+pub const ENOTEMPTY: isize = 39;
+
+/// 内核代替用户程序访问用户内存失败时对应的错误码
+pub fn user_access_errno(err: UserAccessError) -> isize {
+    match err {
+        UserAccessError::Fault => EFAULT,
+        UserAccessError::OutOfMemory => ENOMEM,
+    }
+}
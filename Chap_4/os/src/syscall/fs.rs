@@ -1,17 +1,426 @@
+//! File and filesystem-related syscalls
+use super::errno::{
+    user_access_errno, EBADF, EBUSY, EEXIST, EFAULT, EFBIG, EINVAL, EISDIR, ENAMETOOLONG, ENOENT,
+    ENOSPC, ENOTDIR, ENOTEMPTY, EPERM, EPIPE, ERANGE, ESPIPE,
+};
+use crate::fs::{
+    find_inode, make_pipe, open_file, path_string, resolve_path, FileError, Inode, OpenFlags,
+    Stat, NAME_LENGTH_LIMIT,
+};
+use crate::mm::{translated_byte_buffer, translated_refmut, UserBuffer};
+use crate::task::{current_task, current_user_str, current_user_token};
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::mem::size_of;
 
-const FD_STDOUT: usize = 1;
+/// `*at`系列系统调用的`dirfd`取该值时表示相对于当前工作目录
+pub const AT_FDCWD: isize = -100;
+/// `unlinkat`的`flags`中带有该位时删除的是目录
+pub const AT_REMOVEDIR: u32 = 0x200;
+
+/// 把`*at`系列系统调用中的路径解析成从根目录出发的各级名字。
+/// 绝对路径忽略`dirfd`；还不支持以打开的目录为起点，相对路径的`dirfd`只能是AT_FDCWD
+fn resolve_at(dirfd: isize, path: &str) -> Result<Vec<String>, isize> {
+    if path.is_empty() {
+        return Err(-ENOENT);
+    }
+    if !path.starts_with('/') && dirfd != AT_FDCWD {
+        return Err(-EBADF);
+    }
+    let task = current_task().unwrap();
+    let cwd = task.inner_exclusive_access().cwd.clone();
+    Ok(resolve_path(&cwd, path))
+}
+
+/// 找到路径最后一级所在的目录，返回该目录和最后一级的名字，`components`不能为空
+fn lookup_parent(components: &[String]) -> Result<(Arc<Inode>, &str), isize> {
+    let (name, parent) = components.split_last().unwrap();
+    let parent = find_inode(parent).ok_or(-ENOENT)?;
+    if !parent.is_dir() {
+        return Err(-ENOTDIR);
+    }
+    if name.len() > NAME_LENGTH_LIMIT {
+        return Err(-ENAMETOOLONG);
+    }
+    Ok((parent, name.as_str()))
+}
 
 pub fn sys_write(fd: usize, buf: *const u8, len: usize) -> isize {
-    match fd {
-        FD_STDOUT => {
-            let buffers = translated_byte_buffer(current_user_token(), buf, len);
-            for buffer in buffers {
-                print!("{}", core::str::from_utf8(buffer).unwrap());
-            }
-            len as isize
-        },
-        _ => {
-            panic!("Unsupported fd in sys_write!");
+    let token = current_user_token();
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    if fd >= inner.fd_table.len() {
+        return -EBADF;
+    }
+    if let Some(file) = &inner.fd_table[fd] {
+        if !file.writable() {
+            return -EBADF;
         }
+        let file = file.clone();
+        // 写文件的过程中可能会切换到其他进程，先释放掉对当前进程控制块的借用
+        drop(inner);
+        if let Err(err) = task
+            .inner_exclusive_access()
+            .memory_set
+            .prepare_user_read(buf as usize, len)
+        {
+            return -user_access_errno(err);
+        }
+        let buffers = match translated_byte_buffer(token, buf, len) {
+            Some(buffers) => buffers,
+            None => return -EFAULT,
+        };
+        match file.write(UserBuffer::new(buffers)) {
+            Ok(write_size) => write_size as isize,
+            Err(FileError::BrokenPipe) => -EPIPE,
+            Err(FileError::FileTooLarge) => -EFBIG,
+            Err(FileError::NoSpace) => -ENOSPC,
+        }
+    } else {
+        -EBADF
+    }
+}
+
+pub fn sys_read(fd: usize, buf: *const u8, len: usize) -> isize {
+    let token = current_user_token();
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    if fd >= inner.fd_table.len() {
+        return -EBADF;
+    }
+    if let Some(file) = &inner.fd_table[fd] {
+        if !file.readable() {
+            return -EBADF;
+        }
+        let file = file.clone();
+        drop(inner);
+        if let Err(err) = task
+            .inner_exclusive_access()
+            .memory_set
+            .prepare_user_write(buf as usize, len)
+        {
+            return -user_access_errno(err);
+        }
+        let buffers = match translated_byte_buffer(token, buf, len) {
+            Some(buffers) => buffers,
+            None => return -EFAULT,
+        };
+        // 读文件时可能因为没有数据而让出CPU，不能持有对当前进程控制块的借用
+        file.read(UserBuffer::new(buffers)) as isize
+    } else {
+        -EBADF
+    }
+}
+
+/// 打开文件，返回新分配的文件描述符
+pub fn sys_openat(dirfd: isize, path: *const u8, flags: u32) -> isize {
+    let flags = match OpenFlags::from_bits(flags) {
+        Some(flags) => flags,
+        None => return -EINVAL,
+    };
+    let task = current_task().unwrap();
+    let path = match current_user_str(path) {
+        Ok(path) => path,
+        Err(err) => return -user_access_errno(err),
+    };
+    let components = match resolve_at(dirfd, &path) {
+        Ok(components) => components,
+        Err(errno) => return errno,
+    };
+    if !components.is_empty() {
+        if let Err(errno) = lookup_parent(&components) {
+            return errno;
+        }
+    }
+    if let Some(inode) = find_inode(&components) {
+        if inode.is_dir() && flags.read_write().1 {
+            return -EISDIR;
+        }
+    }
+    if let Some(inode) = open_file("/", &path_string(&components), flags) {
+        let mut inner = task.inner_exclusive_access();
+        let fd = inner.alloc_fd();
+        inner.fd_table[fd] = Some(inode);
+        fd as isize
+    } else {
+        -ENOENT
+    }
+}
+
+pub fn sys_close(fd: usize) -> isize {
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    if fd >= inner.fd_table.len() {
+        return -EBADF;
+    }
+    if inner.fd_table[fd].is_none() {
+        return -EBADF;
+    }
+    inner.fd_table[fd].take();
+    0
+}
+
+/// 复制一个文件描述符，新的文件描述符是当前最小的空闲描述符
+pub fn sys_dup(fd: usize) -> isize {
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    if fd >= inner.fd_table.len() {
+        return -EBADF;
+    }
+    if inner.fd_table[fd].is_none() {
+        return -EBADF;
+    }
+    let new_fd = inner.alloc_fd();
+    inner.fd_table[new_fd] = Some(inner.fd_table[fd].as_ref().unwrap().clone());
+    new_fd as isize
+}
+
+/// 创建一个管道，读端和写端的文件描述符依次写入用户传入的数组`pipe`
+pub fn sys_pipe(pipe: *mut usize) -> isize {
+    let task = current_task().unwrap();
+    let token = current_user_token();
+    let mut inner = task.inner_exclusive_access();
+    // 先准备好写回文件描述符的页面，失败时不分配文件描述符
+    if let Err(err) = inner
+        .memory_set
+        .prepare_user_write(pipe as usize, 2 * size_of::<usize>())
+    {
+        return -user_access_errno(err);
+    }
+    let (read_slot, write_slot) = match (
+        translated_refmut(token, pipe),
+        translated_refmut(token, unsafe { pipe.add(1) }),
+    ) {
+        (Some(read_slot), Some(write_slot)) => (read_slot, write_slot),
+        _ => return -EFAULT,
+    };
+    let (pipe_read, pipe_write) = make_pipe();
+    let read_fd = inner.alloc_fd();
+    inner.fd_table[read_fd] = Some(pipe_read);
+    let write_fd = inner.alloc_fd();
+    inner.fd_table[write_fd] = Some(pipe_write);
+    *read_slot = read_fd;
+    *write_slot = write_fd;
+    0
+}
+
+/// 移动文件的读写位置，返回新的位置
+pub fn sys_lseek(fd: usize, offset: isize, whence: usize) -> isize {
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    if fd >= inner.fd_table.len() {
+        return -EBADF;
+    }
+    let file = match &inner.fd_table[fd] {
+        Some(file) => file.clone(),
+        None => return -EBADF,
+    };
+    drop(inner);
+    if file.stat().is_none() {
+        // 管道和标准输入输出不支持定位
+        return -ESPIPE;
+    }
+    match file.seek(offset, whence) {
+        Some(new_offset) => new_offset as isize,
+        None => -EINVAL,
+    }
+}
+
+/// 把文件的元数据写入用户传入的`st`
+pub fn sys_fstat(fd: usize, st: *mut Stat) -> isize {
+    let token = current_user_token();
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    if fd >= inner.fd_table.len() {
+        return -EBADF;
+    }
+    let file = match &inner.fd_table[fd] {
+        Some(file) => file.clone(),
+        None => return -EBADF,
+    };
+    drop(inner);
+    let stat = match file.stat() {
+        Some(stat) => stat,
+        None => return -EINVAL,
+    };
+    if let Err(err) = task
+        .inner_exclusive_access()
+        .memory_set
+        .prepare_user_write(st as usize, size_of::<Stat>())
+    {
+        return -user_access_errno(err);
+    }
+    // Stat可能跨越两个页面，按字节拷贝到用户空间
+    let src = unsafe {
+        core::slice::from_raw_parts(&stat as *const Stat as *const u8, size_of::<Stat>())
+    };
+    let dst_buffers = match translated_byte_buffer(token, st as *const u8, size_of::<Stat>()) {
+        Some(buffers) => buffers,
+        None => return -EFAULT,
+    };
+    let mut copied = 0;
+    for dst in dst_buffers {
+        dst.copy_from_slice(&src[copied..copied + dst.len()]);
+        copied += dst.len();
+    }
+    0
+}
+
+/// 把当前工作目录的绝对路径（以'\0'结尾）写入`buf`，返回写入的字节数，`buf`放不下时返回-ERANGE
+pub fn sys_getcwd(buf: *mut u8, len: usize) -> isize {
+    let token = current_user_token();
+    let task = current_task().unwrap();
+    let mut cwd = task.inner_exclusive_access().cwd.clone();
+    cwd.push('\0');
+    if cwd.len() > len {
+        return -ERANGE;
+    }
+    if let Err(err) = task
+        .inner_exclusive_access()
+        .memory_set
+        .prepare_user_write(buf as usize, cwd.len())
+    {
+        return -user_access_errno(err);
+    }
+    let src = cwd.as_bytes();
+    let dst_buffers = match translated_byte_buffer(token, buf as *const u8, src.len()) {
+        Some(buffers) => buffers,
+        None => return -EFAULT,
+    };
+    let mut copied = 0;
+    for dst in dst_buffers {
+        dst.copy_from_slice(&src[copied..copied + dst.len()]);
+        copied += dst.len();
+    }
+    src.len() as isize
+}
+
+/// 切换当前工作目录
+pub fn sys_chdir(path: *const u8) -> isize {
+    let path = match current_user_str(path) {
+        Ok(path) => path,
+        Err(err) => return -user_access_errno(err),
+    };
+    let components = match resolve_at(AT_FDCWD, &path) {
+        Ok(components) => components,
+        Err(errno) => return errno,
+    };
+    match find_inode(&components) {
+        Some(inode) if inode.is_dir() => {
+            let task = current_task().unwrap();
+            task.inner_exclusive_access().cwd = path_string(&components);
+            0
+        }
+        Some(_) => -ENOTDIR,
+        None => -ENOENT,
+    }
+}
+
+/// 创建目录
+pub fn sys_mkdirat(dirfd: isize, path: *const u8) -> isize {
+    let path = match current_user_str(path) {
+        Ok(path) => path,
+        Err(err) => return -user_access_errno(err),
+    };
+    let components = match resolve_at(dirfd, &path) {
+        Ok(components) => components,
+        Err(errno) => return errno,
+    };
+    if components.is_empty() {
+        // 根目录
+        return -EEXIST;
+    }
+    let (parent, name) = match lookup_parent(&components) {
+        Ok(found) => found,
+        Err(errno) => return errno,
+    };
+    match parent.create_dir(name) {
+        Some(_) => 0,
+        None => -EEXIST,
+    }
+}
+
+/// 为`oldpath`指向的文件创建一个新的硬链接`newpath`，不能为目录创建硬链接
+pub fn sys_linkat(
+    olddirfd: isize,
+    oldpath: *const u8,
+    newdirfd: isize,
+    newpath: *const u8,
+    flags: u32,
+) -> isize {
+    if flags != 0 {
+        return -EINVAL;
+    }
+    let oldpath = match current_user_str(oldpath) {
+        Ok(oldpath) => oldpath,
+        Err(err) => return -user_access_errno(err),
+    };
+    let newpath = match current_user_str(newpath) {
+        Ok(newpath) => newpath,
+        Err(err) => return -user_access_errno(err),
+    };
+    let old_components = match resolve_at(olddirfd, &oldpath) {
+        Ok(components) => components,
+        Err(errno) => return errno,
+    };
+    let new_components = match resolve_at(newdirfd, &newpath) {
+        Ok(components) => components,
+        Err(errno) => return errno,
+    };
+    let target = match find_inode(&old_components) {
+        Some(inode) => inode,
+        None => return -ENOENT,
+    };
+    if target.is_dir() {
+        return -EPERM;
+    }
+    if new_components.is_empty() {
+        return -EEXIST;
+    }
+    let (parent, name) = match lookup_parent(&new_components) {
+        Ok(found) => found,
+        Err(errno) => return errno,
+    };
+    if parent.link(name, &target) {
+        0
+    } else {
+        -EEXIST
+    }
+}
+
+/// 删除一个目录项，`flags`带有AT_REMOVEDIR时删除的是空目录。
+/// 文件在最后一个链接被删除、并且所有打开它的文件描述符都关闭之后才被回收
+pub fn sys_unlinkat(dirfd: isize, path: *const u8, flags: u32) -> isize {
+    if flags & !AT_REMOVEDIR != 0 {
+        return -EINVAL;
+    }
+    let path = match current_user_str(path) {
+        Ok(path) => path,
+        Err(err) => return -user_access_errno(err),
+    };
+    let components = match resolve_at(dirfd, &path) {
+        Ok(components) => components,
+        Err(errno) => return errno,
+    };
+    if components.is_empty() {
+        // 不能删除根目录
+        return -EBUSY;
+    }
+    let (parent, name) = match lookup_parent(&components) {
+        Ok(found) => found,
+        Err(errno) => return errno,
+    };
+    let target = match parent.find(name) {
+        Some(inode) => inode,
+        None => return -ENOENT,
+    };
+    match (target.is_dir(), flags & AT_REMOVEDIR != 0) {
+        (true, false) => return -EISDIR,
+        (false, true) => return -ENOTDIR,
+        (true, true) if !target.is_empty_dir() => return -ENOTEMPTY,
+        _ => {}
     }
+    parent.unlink(name);
+    0
 }
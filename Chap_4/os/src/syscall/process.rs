@@ -1,12 +1,19 @@
 //! App management syscalls
-use crate::task::exit_current_and_run_next;
-use crate::task::suspend_current_and_run_next;
-use crate::timer::get_time_ms;
+use super::errno::{user_access_errno, EFAULT, EINVAL, ENOEXEC, ENOMEM};
+use crate::config::{PAGE_SIZE, USER_SPACE_END};
+use crate::fs::{open_file, OpenFlags};
+use crate::mm::{translated_byte_buffer, translated_refmut, ExecError, MapPermission, VirtAddr};
+use crate::task::{
+    add_task, block_current_and_run_next, current_task, current_user_str, current_user_token,
+    exit_current_and_run_next, suspend_current_and_run_next, TaskInfo,
+};
+use crate::timer::{add_timer, get_time_ms, TimeSpec};
+use alloc::sync::Arc;
 
 /// task exits and submit an exit code
 pub fn sys_exit(exit_code: i32) -> ! {
     println!("[kernel] Application exited with code {}", exit_code);
-    exit_current_and_run_next();
+    exit_current_and_run_next(exit_code);
     panic!("Unreachable in sys_exit");
 }
 
@@ -16,7 +23,315 @@ pub fn sys_yield() -> isize {
     0
 }
 
+/// 和Linux的nanosleep一样睡眠`req`指定的时长，期间进程不在就绪队列中，不占用CPU。
+/// 定时器以毫秒计时，不足1毫秒的部分向上取整；tv_nsec不小于10^9时返回-EINVAL
+pub fn sys_nanosleep(req: *const TimeSpec) -> isize {
+    let task = current_task().unwrap();
+    if let Err(err) = task
+        .inner_exclusive_access()
+        .memory_set
+        .prepare_user_read(req as usize, core::mem::size_of::<TimeSpec>())
+    {
+        return -user_access_errno(err);
+    }
+    let req = match translated_refmut(current_user_token(), req as *mut TimeSpec) {
+        Some(req) => *req,
+        None => return -EFAULT,
+    };
+    if !req.is_valid() {
+        return -EINVAL;
+    }
+    let ms = req.as_ms();
+    if ms == 0 {
+        return sys_yield();
+    }
+    // 睡眠时间很长时不能溢出，到期时间最多为usize::MAX，也就是一直睡下去
+    add_timer(get_time_ms().saturating_add(ms), task);
+    block_current_and_run_next();
+    0
+}
+
+/// 设置当前进程的调度优先级，成功时返回新的优先级，优先级小于2时返回-1
+pub fn sys_set_priority(prio: isize) -> isize {
+    if prio < 2 {
+        return -1;
+    }
+    current_task().unwrap().inner_exclusive_access().priority = prio as usize;
+    prio
+}
+
 /// get time in milliseconds
 pub fn sys_get_time() -> isize {
     get_time_ms() as isize
-}
\ No newline at end of file
+}
+
+/// 调整堆的大小，返回原来的program break，失败时返回-1
+pub fn sys_sbrk(size: i32) -> isize {
+    if let Some(old_brk) = current_task().unwrap().change_program_brk(size) {
+        old_brk as isize
+    } else {
+        -1
+    }
+}
+
+const PROT_READ: usize = 1 << 0;
+const PROT_WRITE: usize = 1 << 1;
+const PROT_EXEC: usize = 1 << 2;
+
+const MAP_SHARED: usize = 0x01;
+const MAP_PRIVATE: usize = 0x02;
+const MAP_ANONYMOUS: usize = 0x20;
+
+/// 在[start, start + len)建立映射，成功时返回start。flags中MAP_SHARED和MAP_PRIVATE必须恰好有一个：
+/// - MAP_ANONYMOUS：匿名内存，忽略fd和offset，只支持MAP_PRIVATE；
+/// - 否则映射fd对应文件从offset开始的内容，offset必须按页对齐，文件必须可读，
+///   MAP_SHARED并且可写的映射还要求文件以可写方式打开。
+/// start没有按页对齐、长度为0、参数不合法、超出用户地址空间或者和已有的映射重叠时返回-1，
+/// 物理内存不足时返回-ENOMEM
+pub fn sys_mmap(
+    start: usize,
+    len: usize,
+    prot: usize,
+    flags: usize,
+    fd: usize,
+    offset: usize,
+) -> isize {
+    let all = PROT_READ | PROT_WRITE | PROT_EXEC;
+    if start % PAGE_SIZE != 0 || len == 0 || prot & !all != 0 || prot & all == 0 {
+        return -1;
+    }
+    if flags & !(MAP_SHARED | MAP_PRIVATE | MAP_ANONYMOUS) != 0 {
+        return -1;
+    }
+    let shared = match flags & (MAP_SHARED | MAP_PRIVATE) {
+        MAP_SHARED => true,
+        MAP_PRIVATE => false,
+        _ => return -1,
+    };
+    let end = match start.checked_add(len) {
+        Some(end) if end <= USER_SPACE_END => end,
+        _ => return -1,
+    };
+    let mut permission = MapPermission::U;
+    if prot & PROT_READ != 0 {
+        permission |= MapPermission::R;
+    }
+    // RISC-V中可写但不可读的页表项是保留的，可写的页面同时也可读
+    if prot & PROT_WRITE != 0 {
+        permission |= MapPermission::R | MapPermission::W;
+    }
+    if prot & PROT_EXEC != 0 {
+        permission |= MapPermission::X;
+    }
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    let ok = if flags & MAP_ANONYMOUS != 0 {
+        if shared {
+            return -1;
+        }
+        match inner
+            .memory_set
+            .mmap(VirtAddr(start), VirtAddr(end), permission)
+        {
+            Ok(ok) => ok,
+            Err(_) => return -ENOMEM,
+        }
+    } else {
+        if offset % PAGE_SIZE != 0 {
+            return -1;
+        }
+        let file = match inner.fd_table.get(fd) {
+            Some(Some(file)) => file.clone(),
+            _ => return -1,
+        };
+        let inode = match file.inode() {
+            Some(inode) if !inode.is_dir() => inode,
+            _ => return -1,
+        };
+        if !file.readable() || (shared && prot & PROT_WRITE != 0 && !file.writable()) {
+            return -1;
+        }
+        inner.memory_set.mmap_file(
+            VirtAddr(start),
+            VirtAddr(end),
+            permission,
+            inode,
+            offset,
+            shared,
+        )
+    };
+    if ok {
+        start as isize
+    } else {
+        -1
+    }
+}
+
+/// 解除[start, start + len)的映射，范围内有没有映射的页面时返回-1
+pub fn sys_munmap(start: usize, len: usize) -> isize {
+    if start % PAGE_SIZE != 0 || len == 0 {
+        return -1;
+    }
+    let end = match start.checked_add(len) {
+        Some(end) if end <= USER_SPACE_END => end,
+        _ => return -1,
+    };
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    if inner.memory_set.munmap(VirtAddr(start), VirtAddr(end)) {
+        0
+    } else {
+        -1
+    }
+}
+
+/// 把[start, start + len)中MAP_SHARED文件映射被写过的页面写回文件，范围内有没有映射的页面时返回-1
+pub fn sys_msync(start: usize, len: usize) -> isize {
+    if start % PAGE_SIZE != 0 {
+        return -1;
+    }
+    let end = match start.checked_add(len) {
+        Some(end) if end <= USER_SPACE_END => end,
+        _ => return -1,
+    };
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    if inner.memory_set.msync(VirtAddr(start), VirtAddr(end)) {
+        0
+    } else {
+        -1
+    }
+}
+
+pub fn sys_getpid() -> isize {
+    current_task().unwrap().pid.0 as isize
+}
+
+/// 父进程返回子进程的pid，子进程返回0，物理内存不足时返回-ENOMEM
+pub fn sys_fork() -> isize {
+    let current_task = current_task().unwrap();
+    let new_task = match current_task.fork() {
+        Ok(new_task) => new_task,
+        Err(_) => return -ENOMEM,
+    };
+    let new_pid = new_task.pid.0;
+    let trap_cx = new_task.inner_exclusive_access().get_trap_cx();
+    // 子进程从fork返回时a0为0
+    trap_cx.x[10] = 0;
+    add_task(new_task);
+    new_pid as isize
+}
+
+/// 成功时不会返回（trap上下文已被替换），找不到应用时返回-1，
+/// 不是合法的ELF文件时返回-ENOEXEC，物理内存不足时返回-ENOMEM
+pub fn sys_exec(path: *const u8) -> isize {
+    let path = match current_user_str(path) {
+        Ok(path) => path,
+        Err(err) => return -user_access_errno(err),
+    };
+    let task = current_task().unwrap();
+    let cwd = task.inner_exclusive_access().cwd.clone();
+    // 不含'/'的应用名在当前目录下找不到时，再到根目录下找，类似于只有"/"的PATH
+    let app_inode = open_file(&cwd, &path, OpenFlags::RDONLY).or_else(|| {
+        if path.contains('/') {
+            None
+        } else {
+            open_file("/", &path, OpenFlags::RDONLY)
+        }
+    });
+    if let Some(app_inode) = app_inode.filter(|inode| !inode.is_dir()) {
+        let all_data = app_inode.read_all();
+        match task.exec(all_data.as_slice()) {
+            Ok(()) => 0,
+            Err(ExecError::BadElf) => -ENOEXEC,
+            Err(ExecError::OutOfMemory) => -ENOMEM,
+        }
+    } else {
+        -1
+    }
+}
+
+/// 等待pid为`pid`的子进程退出（pid为-1时等待任意子进程），回收它并写回退出码
+/// 不存在这样的子进程时返回-1，子进程都还没有退出时返回-2
+pub fn sys_waitpid(pid: isize, exit_code_ptr: *mut i32) -> isize {
+    let task = current_task().unwrap();
+
+    let mut inner = task.inner_exclusive_access();
+    if inner
+        .children
+        .iter()
+        .find(|p| pid == -1 || pid as usize == p.getpid())
+        .is_none()
+    {
+        return -1;
+    }
+    let pair = inner.children.iter().enumerate().find(|(_, p)| {
+        p.inner_exclusive_access().is_zombie() && (pid == -1 || pid as usize == p.getpid())
+    });
+    if let Some((idx, _)) = pair {
+        // 先准备好写回退出码的页面，物理内存不足时子进程留给下一次waitpid回收
+        if let Err(err) = inner
+            .memory_set
+            .prepare_user_write(exit_code_ptr as usize, core::mem::size_of::<i32>())
+        {
+            return -user_access_errno(err);
+        }
+        let exit_code_slot = match translated_refmut(inner.memory_set.token(), exit_code_ptr) {
+            Some(slot) => slot,
+            None => return -EFAULT,
+        };
+        let child = inner.children.remove(idx);
+        // 此时子进程只剩这一个引用，离开作用域后它的所有资源都会被回收
+        assert_eq!(Arc::strong_count(&child), 1);
+        let found_pid = child.getpid();
+        let exit_code = child.inner_exclusive_access().exit_code;
+        *exit_code_slot = exit_code;
+        found_pid as isize
+    } else {
+        -2
+    }
+}
+
+/// 把pid对应进程的运行统计写入`ti`，只能查询当前进程和它还没有被回收的子进程，其他pid返回-1
+pub fn sys_task_info(pid: usize, ti: *mut TaskInfo) -> isize {
+    let task = current_task().unwrap();
+    let target = if pid == task.getpid() {
+        task.clone()
+    } else {
+        match task
+            .inner_exclusive_access()
+            .children
+            .iter()
+            .find(|child| child.getpid() == pid)
+        {
+            Some(child) => child.clone(),
+            None => return -1,
+        }
+    };
+    if let Err(err) = task
+        .inner_exclusive_access()
+        .memory_set
+        .prepare_user_write(ti as usize, core::mem::size_of::<TaskInfo>())
+    {
+        return -user_access_errno(err);
+    }
+    let token = current_user_token();
+    let inner = target.inner_exclusive_access();
+    // TaskInfo可能跨越两个页面，按字节拷贝到用户空间
+    let src = unsafe {
+        core::slice::from_raw_parts(
+            inner.stats.info.as_ref() as *const TaskInfo as *const u8,
+            core::mem::size_of::<TaskInfo>(),
+        )
+    };
+    let dst_buffers = match translated_byte_buffer(token, ti as *const u8, src.len()) {
+        Some(buffers) => buffers,
+        None => return -EFAULT,
+    };
+    let mut copied = 0;
+    for dst in dst_buffers {
+        dst.copy_from_slice(&src[copied..copied + dst.len()]);
+        copied += dst.len();
+    }
+    0
+}
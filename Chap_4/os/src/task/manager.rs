@@ -0,0 +1,63 @@
+use super::scheduler::Scheduler;
+#[cfg(feature = "fifo_scheduler")]
+use super::scheduler::FifoScheduler;
+#[cfg(feature = "mlfq_scheduler")]
+use super::scheduler::MlfqScheduler;
+#[cfg(not(any(feature = "fifo_scheduler", feature = "mlfq_scheduler")))]
+use super::scheduler::StrideScheduler;
+use super::TaskControlBlock;
+use crate::sync::UPSafeCell;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use lazy_static::*;
+
+// 默认使用Stride调度，启用fifo_scheduler或mlfq_scheduler特性时使用FIFO调度或多级反馈队列
+#[cfg(all(feature = "fifo_scheduler", feature = "mlfq_scheduler"))]
+compile_error!("features `fifo_scheduler` and `mlfq_scheduler` are mutually exclusive");
+#[cfg(not(any(feature = "fifo_scheduler", feature = "mlfq_scheduler")))]
+type SchedulerImpl = StrideScheduler;
+#[cfg(feature = "fifo_scheduler")]
+type SchedulerImpl = FifoScheduler;
+#[cfg(feature = "mlfq_scheduler")]
+type SchedulerImpl = MlfqScheduler;
+
+/// 任务管理器只负责管理所有处于就绪态的进程，运行顺序交给调度器决定
+pub struct TaskManager {
+    scheduler: SchedulerImpl,
+}
+
+impl TaskManager {
+    pub fn new() -> Self {
+        Self {
+            scheduler: SchedulerImpl::new(),
+        }
+    }
+    pub fn add(&mut self, task: Arc<TaskControlBlock>) {
+        self.scheduler.add(task);
+    }
+    pub fn fetch(&mut self) -> Option<Arc<TaskControlBlock>> {
+        self.scheduler.fetch()
+    }
+    /// 所有就绪进程，供OOM killer挑选
+    pub fn ready_tasks(&self) -> Vec<Arc<TaskControlBlock>> {
+        self.scheduler.ready_tasks()
+    }
+}
+
+lazy_static! {
+    pub static ref TASK_MANAGER: UPSafeCell<TaskManager> = unsafe {
+        UPSafeCell::new(TaskManager::new())
+    };
+}
+
+pub fn add_task(task: Arc<TaskControlBlock>) {
+    TASK_MANAGER.exclusive_access().add(task);
+}
+
+pub fn fetch_task() -> Option<Arc<TaskControlBlock>> {
+    TASK_MANAGER.exclusive_access().fetch()
+}
+
+pub fn ready_tasks() -> Vec<Arc<TaskControlBlock>> {
+    TASK_MANAGER.exclusive_access().ready_tasks()
+}
@@ -1,156 +1,204 @@
 mod context;
+mod manager;
+mod pid;
+mod processor;
+mod scheduler;
+mod stats;
 mod switch;
 
 #[allow(clipper::module_inception)]
 mod task;
 
-use crate::loader::{get_num_app, init_app_cx};
-use crate::sync::UPSafeCell;
+use crate::fs::{open_file, OpenFlags};
+use crate::mm::{translated_str, Access, OutOfMemory, UserAccessError, VirtAddr};
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
 use lazy_static::*;
+use manager::ready_tasks;
 use switch::__switch;
-use crate::config::*;
-use task::{TaskControlBlock, TaskStatus};
+use task::TaskStatus;
 
 pub use context::TaskContext;
+pub use manager::{add_task, fetch_task};
+pub use pid::{pid_alloc, KernelStack, PidHandle};
+pub use stats::TaskInfo;
+pub use task::TaskControlBlock;
+pub use processor::{
+    current_task, current_trap_cx, current_user_token, run_tasks, schedule, take_current_task,
+};
+use processor::set_exited_task;
 
-pub struct TaskManager {
-    num_app: usize,
-    inner: UPSafeCell<TaskManagerInner>,
-}
+pub fn suspend_current_and_run_next() {
+    let task = take_current_task().unwrap();
 
-struct TaskManagerInner{
-    tasks: Vec<TaskControlBlock>,
-    current_task: usize,
+    let mut task_inner = task.inner_exclusive_access();
+    let task_cx_ptr = &mut task_inner.task_cx as *mut TaskContext;
+    task_inner.task_status = TaskStatus::Ready;
+    task_inner.stats.switch_out();
+    drop(task_inner);
+
+    // 放回就绪队列的末尾
+    add_task(task);
+    schedule(task_cx_ptr);
 }
 
-lazy_static! {
-    pub static ref TASKMANAGER: TaskManager = {
-        println!("init TASK_MANAGER");
-        let num_app = get_num_app();       // get_num_app(): 来自loader.rs
-        println!("num_app = {}", num_app);
-        let mut tasks: Vec<TaskControlBlock> = Vec::new();
-        for i in 0..num_app {
-            tasks.push(TaskControlBlock::new(
-                get_app_data(i),
-                i,
-            ));
-        }
-        TaskManager {
-            num_app,
-            inner: unsafe {
-                UPSafeCell::new(TaskManagerInner {
-                    tasks,
-                    current_task: 0,
-                })
-            },
-        }
-    };
+/// 阻塞当前进程，调用之前需要把它交给某个等待队列（例如定时器队列），否则它再也不会被调度
+pub fn block_current_and_run_next() {
+    let task = take_current_task().unwrap();
+    let mut task_inner = task.inner_exclusive_access();
+    let task_cx_ptr = &mut task_inner.task_cx as *mut TaskContext;
+    task_inner.task_status = TaskStatus::Blocked;
+    task_inner.stats.switch_out();
+    drop(task_inner);
+    schedule(task_cx_ptr);
 }
 
-pub fn suspend_current_and_run_next() {
-    mark_current_suspend();
-    run_next_task();
+/// 唤醒一个被阻塞的进程，放回就绪队列
+pub fn wakeup_task(task: Arc<TaskControlBlock>) {
+    task.inner_exclusive_access().task_status = TaskStatus::Ready;
+    add_task(task);
 }
 
-pub fn exit_current_and_run_next() {
-    mark_current_exited();
-    run_next_task();
+/// 记账：当前进程从用户态陷入内核
+pub fn account_trap_enter() {
+    current_task().unwrap().inner_exclusive_access().stats.trap_enter();
 }
 
-pub fn mark_current_suspend() {
-    TASKMANAGER.mark_current_suspend();
+/// 记账：当前进程从内核返回用户态
+pub fn account_trap_return() {
+    current_task().unwrap().inner_exclusive_access().stats.trap_return();
 }
 
-pub fn mark_current_exited() {
-    TASKMANAGER.mark_current_exited();
+/// 记账：当前进程发起了一次系统调用
+pub fn account_syscall(syscall_id: usize) {
+    current_task()
+        .unwrap()
+        .inner_exclusive_access()
+        .stats
+        .record_syscall(syscall_id);
 }
 
-fn run_next_task() {
-    TASKMANAGER.run_next_task();
+/// 当前进程用完了时间片，被时钟中断抢占
+pub fn preempt_current_and_run_next() {
+    current_task().unwrap().inner_exclusive_access().preempted = true;
+    suspend_current_and_run_next();
 }
 
-impl TaskManager {
-    fn mark_current_suspend(&self) {
-        let mut inner = self.inner.exclusive_access();
-        let current = inner.current_task;
-        inner.tasks[current].task_status = TaskStatus::Ready;
+pub fn exit_current_and_run_next(exit_code: i32) {
+    let task = take_current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    // 变为僵尸进程，等待父进程通过waitpid回收
+    inner.task_status = TaskStatus::Zombie;
+    inner.exit_code = exit_code;
+    inner.stats.switch_out();
+    inner.stats.print_summary(task.getpid());
+
+    // 子进程成为孤儿，全部挂到initproc下，由initproc负责回收
+    {
+        let mut initproc_inner = INITPROC.inner_exclusive_access();
+        for child in inner.children.iter() {
+            child.inner_exclusive_access().parent = Some(Arc::downgrade(&INITPROC));
+            initproc_inner.children.push(child.clone());
+        }
     }
+    inner.children.clear();
+    // 关闭所有打开的文件，这样管道的读端才能及时读到EOF
+    inner.fd_table.clear();
+    // 提前回收用户地址空间占用的物理页帧，页表等到父进程回收时再释放
+    inner.memory_set.recycle_data_pages();
+    drop(inner);
+    // 没有父进程时这是最后一个引用，直接drop会在使用中的内核栈上将其解除映射
+    set_exited_task(task);
+    // 当前进程不会再被调度，它的任务上下文不需要保存
+    let mut _unused = TaskContext::zero_init();
+    schedule(&mut _unused as *mut _);
+}
 
-    fn mark_current_exited(&self) {
-        let mut inner = self.inner.exclusive_access();
-        let current = inner.current_task;
-        inner.tasks[current].task_status = TaskStatus::Exited;
+/// 处理当前进程访问`va`时的缺页异常，处理成功时返回true。
+/// 物理内存不足时由OOM killer结束一个进程，之后同样返回true，让用户程序重新执行这条访存指令
+pub fn handle_page_fault(va: usize, access: Access) -> bool {
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    let result = inner
+        .memory_set
+        .handle_page_fault(VirtAddr::from(va).floor(), access);
+    drop(inner);
+    drop(task);
+    match result {
+        Ok(handled) => handled,
+        Err(OutOfMemory) => {
+            oom_kill();
+            true
+        }
     }
+}
 
-    fn run_next_task(&self) {
-        
-        if let Some(next) = self.find_next_task(){
-            let mut inner = self.inner.exclusive_access();
-            let current = inner.current_task;
-            inner.tasks[next].task_status = TaskStatus::Running;
-            inner.current_task = next;
-            let current_task_cx_ptr = &mut inner.tasks[current].task_cx as *mut TaskContext;
-            let next_task_cx_ptr = &inner.tasks[next].task_cx as *const TaskContext;
+/// OOM killer：在当前进程和就绪进程中选出占用物理页帧最多的一个结束掉，initproc不会被选中。
+/// 选中其他进程时只做标记，它下次返回用户态之前自行退出，当前进程先让出CPU等它释放内存
+fn oom_kill() {
+    let current = current_task().unwrap();
+    let mut candidates = Vec::new();
+    for task in core::iter::once(current.clone()).chain(ready_tasks()) {
+        if Arc::ptr_eq(&task, &INITPROC) {
+            continue;
+        }
+        let inner = task.inner_exclusive_access();
+        if inner.killed {
+            // 上一个被选中的进程还没有退出，不必再结束一个
             drop(inner);
-            
-            unsafe {
-                __switch(
-                    current_task_cx_ptr,
-                    next_task_cx_ptr,
-                );
-            }
-        } else{
-            panic!("All applications completes!");
+            drop(current);
+            suspend_current_and_run_next();
+            return;
         }
-    }
-
-    fn find_next_task(&self) -> Option<usize> {
-        let inner = self.inner.exclusive_access();
-        let current = inner.current_task;
-        (current + 1..current + self.num_app + 1)    // 从current+1开始检查，检查一圈
-            .map(|id| id % self.num_app)
-            .find(|id| {
-                inner.tasks[*id].task_status == TaskStatus::Ready
-            })
-    }
-
-    fn run_first_task(&self) -> !{
-        let mut inner = self.inner.exclusive_access();
-        let task0 = &mut inner.tasks[0];
-        task0.task_status = TaskStatus::Running;
-        let next_task_cx_ptr = &task0.task_cx as *const TaskContext;
+        let pages = inner.memory_set.resident_pages();
         drop(inner);
-        let mut _unused = TaskContext::zero_init();
-
-        unsafe {
-            __switch(
-                &mut _unused as *mut TaskContext,
-                next_task_cx_ptr,
-            );
-        }
-
-        panic!("unreachable in run_first_task!");
+        candidates.push((pages, task));
     }
-
-    fn get_current_token(&self) -> usize {
-        let inner = self.inner.borrow();
-        let current = inner.current_task;
-        inner.tasks[current].get_user_token()
-    }
-    fn get_current_trap_cx(&self) -> &mut TrapContext {
-        let inner = self.inner.borrow();
-        let current = inner.current_task;
-        inner.tasks[current].get_trap_cx()
+    let (pages, victim) = candidates
+        .into_iter()
+        .max_by_key(|(pages, _)| *pages)
+        .expect("[kernel] Out of memory, and no process can be killed!");
+    println!(
+        "[kernel] Out of memory, killed process {} which used {} pages.",
+        victim.getpid(),
+        pages
+    );
+    if Arc::ptr_eq(&victim, &current) {
+        drop(victim);
+        drop(current);
+        exit_current_and_run_next(-9);
+    } else {
+        victim.inner_exclusive_access().killed = true;
+        drop(victim);
+        drop(current);
+        suspend_current_and_run_next();
     }
 }
 
-pub fn run_first_task() {
-    TASKMANAGER.run_first_task();
+/// 当前进程是否已经被OOM killer选中，在内核中等待的进程据此尽快返回
+pub fn current_killed() -> bool {
+    current_task().unwrap().inner_exclusive_access().killed
+}
+
+/// 读取当前进程地址空间中以'\0'结尾的字符串，所在的页面还不在内存中时先处理缺页，
+/// 字符串所在的页面用户程序不能读取或者物理内存不足时返回Err
+pub fn current_user_str(ptr: *const u8) -> Result<String, UserAccessError> {
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    inner.memory_set.prepare_user_str(ptr as usize)?;
+    translated_str(inner.memory_set.token(), ptr).ok_or(UserAccessError::Fault)
 }
 
-pub fn current_user_token() -> usize{
-    TASKMANAGER.get_current_token()
+lazy_static! {
+    pub static ref INITPROC: Arc<TaskControlBlock> = Arc::new({
+        let inode = open_file("/", "initproc", OpenFlags::RDONLY).unwrap();
+        let v = inode.read_all();
+        TaskControlBlock::new(v.as_slice())
+    });
 }
-pub fn current_trap_cx() -> &'static mut TrapContext {
-    TASKMANAGER.get_current_trap_cx()
+
+/// 内核启动时只创建initproc这一个进程，其他进程都由它fork/exec而来
+pub fn add_initproc() {
+    add_task(INITPROC.clone());
 }
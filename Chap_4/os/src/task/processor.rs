@@ -0,0 +1,113 @@
+use super::__switch;
+use super::{fetch_task, TaskStatus};
+use super::{TaskContext, TaskControlBlock};
+use crate::sync::UPSafeCell;
+use crate::timer::{check_timer, set_next_trigger};
+use crate::trap::TrapContext;
+use alloc::sync::Arc;
+use core::arch::asm;
+use lazy_static::*;
+use riscv::register::sip;
+
+/// 处理器管理结构：维护CPU当前正在执行的进程，以及idle控制流的任务上下文
+pub struct Processor {
+    current: Option<Arc<TaskControlBlock>>,
+    idle_task_cx: TaskContext,
+    /// 刚刚退出的进程，等回到idle控制流之后再释放，因为退出时仍在使用它的内核栈
+    exited_task: Option<Arc<TaskControlBlock>>,
+}
+
+impl Processor {
+    pub fn new() -> Self {
+        Self {
+            current: None,
+            idle_task_cx: TaskContext::zero_init(),
+            exited_task: None,
+        }
+    }
+    fn get_idle_task_cx_ptr(&mut self) -> *mut TaskContext {
+        &mut self.idle_task_cx as *mut _
+    }
+    pub fn take_current(&mut self) -> Option<Arc<TaskControlBlock>> {
+        self.current.take()
+    }
+    pub fn current(&self) -> Option<Arc<TaskControlBlock>> {
+        self.current.as_ref().map(|task| Arc::clone(task))
+    }
+}
+
+lazy_static! {
+    pub static ref PROCESSOR: UPSafeCell<Processor> = unsafe { UPSafeCell::new(Processor::new()) };
+}
+
+/// idle控制流：不断从任务管理器中取出进程并切换过去执行
+pub fn run_tasks() {
+    loop {
+        // 内核中不响应中断，所有进程都在睡眠时只能由idle控制流唤醒到期的进程
+        check_timer();
+        let mut processor = PROCESSOR.exclusive_access();
+        if let Some(task) = fetch_task() {
+            let idle_task_cx_ptr = processor.get_idle_task_cx_ptr();
+            let mut task_inner = task.inner_exclusive_access();
+            let next_task_cx_ptr = &task_inner.task_cx as *const TaskContext;
+            task_inner.task_status = TaskStatus::Running;
+            task_inner.stats.switch_in();
+            drop(task_inner);
+            processor.current = Some(task);
+            // 切换之前必须手动释放，否则换到其他进程之后就再也没有机会释放了
+            drop(processor);
+            unsafe {
+                __switch(idle_task_cx_ptr, next_task_cx_ptr);
+            }
+            // 已经切换回idle控制流的栈上，可以安全地回收退出进程的内核栈了
+            PROCESSOR.exclusive_access().exited_task.take();
+        } else {
+            drop(processor);
+            // 没有就绪进程时停下来等待中断。sstatus.SIE为0，中断不会被响应，
+            // 但sie中打开的中断到来时wfi仍会返回
+            unsafe {
+                asm!("wfi");
+            }
+            if sip::read().stimer() {
+                // 重新设置时钟，清除sip.stip，否则wfi会立即返回
+                set_next_trigger();
+            }
+        }
+    }
+}
+
+pub fn take_current_task() -> Option<Arc<TaskControlBlock>> {
+    PROCESSOR.exclusive_access().take_current()
+}
+
+/// 暂存退出的进程，避免在它的内核栈上释放它
+pub fn set_exited_task(task: Arc<TaskControlBlock>) {
+    PROCESSOR.exclusive_access().exited_task = Some(task);
+}
+
+pub fn current_task() -> Option<Arc<TaskControlBlock>> {
+    PROCESSOR.exclusive_access().current()
+}
+
+pub fn current_user_token() -> usize {
+    let task = current_task().unwrap();
+    let token = task.inner_exclusive_access().get_user_token();
+    token
+}
+
+pub fn current_trap_cx() -> &'static mut TrapContext {
+    current_task()
+        .unwrap()
+        .inner_exclusive_access()
+        .get_trap_cx()
+}
+
+/// 当前进程的时间片用完或者退出时，切换回idle控制流，由它来选择下一个进程
+pub fn schedule(switched_task_cx_ptr: *mut TaskContext) {
+    let mut processor = PROCESSOR.exclusive_access();
+    let idle_task_cx_ptr = processor.get_idle_task_cx_ptr();
+    drop(processor);
+    unsafe {
+        __switch(switched_task_cx_ptr, idle_task_cx_ptr);
+    }
+}
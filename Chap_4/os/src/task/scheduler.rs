@@ -0,0 +1,179 @@
+//! 调度器决定就绪进程的运行顺序，编译时通过cargo特性选择具体的实现
+use super::TaskControlBlock;
+use crate::timer::get_time_ms;
+use alloc::collections::{BinaryHeap, VecDeque};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+
+pub trait Scheduler {
+    fn new() -> Self;
+    /// 把一个进程放入就绪队列
+    fn add(&mut self, task: Arc<TaskControlBlock>);
+    /// 取出下一个要运行的进程
+    fn fetch(&mut self) -> Option<Arc<TaskControlBlock>>;
+    /// 所有就绪进程
+    fn ready_tasks(&self) -> Vec<Arc<TaskControlBlock>>;
+}
+
+/// 简单的FIFO（Round-Robin）调度
+#[cfg_attr(not(feature = "fifo_scheduler"), allow(dead_code))]
+pub struct FifoScheduler {
+    ready_queue: VecDeque<Arc<TaskControlBlock>>,
+}
+
+impl Scheduler for FifoScheduler {
+    fn new() -> Self {
+        Self {
+            ready_queue: VecDeque::new(),
+        }
+    }
+    fn add(&mut self, task: Arc<TaskControlBlock>) {
+        self.ready_queue.push_back(task);
+    }
+    fn fetch(&mut self) -> Option<Arc<TaskControlBlock>> {
+        self.ready_queue.pop_front()
+    }
+    fn ready_tasks(&self) -> Vec<Arc<TaskControlBlock>> {
+        self.ready_queue.iter().cloned().collect()
+    }
+}
+
+/// 优先级为p的进程每运行一次，它的pass增加BIG_STRIDE / p
+#[cfg_attr(any(feature = "fifo_scheduler", feature = "mlfq_scheduler"), allow(dead_code))]
+pub const BIG_STRIDE: usize = 1 << 20;
+/// 进程的默认优先级
+pub const DEFAULT_PRIORITY: usize = 16;
+
+/// 就绪队列中的一项，按pass从小到大出队，pass相同时先入队的先出队
+#[cfg_attr(any(feature = "fifo_scheduler", feature = "mlfq_scheduler"), allow(dead_code))]
+struct StrideEntry {
+    pass: usize,
+    seq: usize,
+    task: Arc<TaskControlBlock>,
+}
+
+impl PartialEq for StrideEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for StrideEntry {}
+
+impl PartialOrd for StrideEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for StrideEntry {
+    /// BinaryHeap是大根堆，这里反过来比较
+    fn cmp(&self, other: &Self) -> Ordering {
+        (other.pass, other.seq).cmp(&(self.pass, self.seq))
+    }
+}
+
+/// Stride调度：每次选出pass最小的进程运行，之后它的pass增加一个stride，
+/// 长期来看每个进程得到的时间片数和它的优先级成正比
+#[cfg_attr(any(feature = "fifo_scheduler", feature = "mlfq_scheduler"), allow(dead_code))]
+pub struct StrideScheduler {
+    ready_queue: BinaryHeap<StrideEntry>,
+    seq: usize,
+    /// 最近一次出队的pass，就绪进程的pass都不会比它小
+    min_pass: usize,
+}
+
+impl Scheduler for StrideScheduler {
+    fn new() -> Self {
+        Self {
+            ready_queue: BinaryHeap::new(),
+            seq: 0,
+            min_pass: 0,
+        }
+    }
+    fn add(&mut self, task: Arc<TaskControlBlock>) {
+        // 进程在就绪队列中时pass不会变化。睡眠醒来的进程pass可能远远落后，
+        // 不拉到min_pass的话它会连续占用很多个时间片
+        let mut inner = task.inner_exclusive_access();
+        inner.pass = inner.pass.max(self.min_pass);
+        let pass = inner.pass;
+        drop(inner);
+        self.ready_queue.push(StrideEntry {
+            pass,
+            seq: self.seq,
+            task,
+        });
+        self.seq += 1;
+    }
+    fn fetch(&mut self) -> Option<Arc<TaskControlBlock>> {
+        let entry = self.ready_queue.pop()?;
+        self.min_pass = entry.pass;
+        let task = entry.task;
+        let mut inner = task.inner_exclusive_access();
+        inner.pass += BIG_STRIDE / inner.priority;
+        drop(inner);
+        Some(task)
+    }
+    fn ready_tasks(&self) -> Vec<Arc<TaskControlBlock>> {
+        self.ready_queue.iter().map(|entry| entry.task.clone()).collect()
+    }
+}
+
+/// MLFQ的队列数，第0级优先级最高
+pub const MLFQ_LEVELS: usize = 3;
+/// 每隔这么长时间把所有进程提升到第0级，避免低优先级的进程饿死
+const MLFQ_BOOST_INTERVAL_MS: usize = 1000;
+
+/// 多级反馈队列：总是先运行高优先级队列中的进程，同一级内FIFO。
+/// 用完整个时间片被时钟中断抢占的进程降一级，时间片用完之前主动让出CPU的进程升一级，
+/// 这样频繁让出CPU的交互式进程能够比CPU密集型进程更快地得到响应
+#[cfg_attr(not(feature = "mlfq_scheduler"), allow(dead_code))]
+pub struct MlfqScheduler {
+    queues: Vec<VecDeque<Arc<TaskControlBlock>>>,
+    last_boost_ms: usize,
+}
+
+impl MlfqScheduler {
+    /// 把所有就绪进程放回第0级。正在运行的进程不在队列中，它在下一次入队时按自己的表现调整
+    fn boost(&mut self) {
+        for level in 1..MLFQ_LEVELS {
+            while let Some(task) = self.queues[level].pop_front() {
+                task.inner_exclusive_access().queue_level = 0;
+                self.queues[0].push_back(task);
+            }
+        }
+    }
+}
+
+impl Scheduler for MlfqScheduler {
+    fn new() -> Self {
+        Self {
+            queues: (0..MLFQ_LEVELS).map(|_| VecDeque::new()).collect(),
+            last_boost_ms: 0,
+        }
+    }
+    fn add(&mut self, task: Arc<TaskControlBlock>) {
+        let mut inner = task.inner_exclusive_access();
+        if inner.preempted {
+            inner.queue_level = (inner.queue_level + 1).min(MLFQ_LEVELS - 1);
+        } else {
+            inner.queue_level = inner.queue_level.saturating_sub(1);
+        }
+        inner.preempted = false;
+        let level = inner.queue_level;
+        drop(inner);
+        self.queues[level].push_back(task);
+    }
+    fn fetch(&mut self) -> Option<Arc<TaskControlBlock>> {
+        let now = get_time_ms();
+        if now - self.last_boost_ms >= MLFQ_BOOST_INTERVAL_MS {
+            self.boost();
+            self.last_boost_ms = now;
+        }
+        self.queues.iter_mut().find_map(|queue| queue.pop_front())
+    }
+    fn ready_tasks(&self) -> Vec<Arc<TaskControlBlock>> {
+        self.queues.iter().flatten().cloned().collect()
+    }
+}
@@ -0,0 +1,84 @@
+//! 进程运行情况的统计，通过sys_task_info查询，进程退出时打印汇总
+use crate::config::MAX_SYSCALL_NUM;
+use crate::timer::get_time_us;
+use alloc::boxed::Box;
+use alloc::string::String;
+use core::fmt::Write;
+
+/// sys_task_info写回用户空间的结构，用户库中有相同布局的定义
+#[repr(C)]
+pub struct TaskInfo {
+    pub user_time_us: usize,
+    pub kernel_time_us: usize,
+    /// 第一次被调度执行的时刻（开机以来的微秒数），还没有运行过时为0
+    pub first_run_us: usize,
+    /// 被调度执行的次数
+    pub switches: usize,
+    /// 每个系统调用被调用的次数，下标为系统调用编号
+    pub syscall_times: [u32; MAX_SYSCALL_NUM],
+}
+
+pub struct TaskStats {
+    /// 有2KiB左右，放在堆上，避免创建进程控制块时在内核栈上来回拷贝
+    pub info: Box<TaskInfo>,
+    /// 当前这一段用户态或内核态时间的起点
+    timestamp_us: usize,
+}
+
+impl TaskStats {
+    pub fn new() -> Self {
+        Self {
+            info: Box::new(TaskInfo {
+                user_time_us: 0,
+                kernel_time_us: 0,
+                first_run_us: 0,
+                switches: 0,
+                syscall_times: [0; MAX_SYSCALL_NUM],
+            }),
+            timestamp_us: 0,
+        }
+    }
+    /// 被调度执行，开始计算内核态时间
+    pub fn switch_in(&mut self) {
+        let now = get_time_us();
+        if self.info.first_run_us == 0 {
+            self.info.first_run_us = now;
+        }
+        self.info.switches += 1;
+        self.timestamp_us = now;
+    }
+    /// 让出CPU或者退出，其他进程运行的时间不算在内
+    pub fn switch_out(&mut self) {
+        self.info.kernel_time_us += get_time_us() - self.timestamp_us;
+    }
+    /// 从用户态陷入内核
+    pub fn trap_enter(&mut self) {
+        let now = get_time_us();
+        self.info.user_time_us += now - self.timestamp_us;
+        self.timestamp_us = now;
+    }
+    /// 从内核返回用户态
+    pub fn trap_return(&mut self) {
+        let now = get_time_us();
+        self.info.kernel_time_us += now - self.timestamp_us;
+        self.timestamp_us = now;
+    }
+    pub fn record_syscall(&mut self, syscall_id: usize) {
+        if syscall_id < MAX_SYSCALL_NUM {
+            self.info.syscall_times[syscall_id] += 1;
+        }
+    }
+    /// 进程退出时打印汇总，系统调用以“编号*次数”的形式列出
+    pub fn print_summary(&self, pid: usize) {
+        let mut syscalls = String::new();
+        for (id, times) in self.info.syscall_times.iter().enumerate() {
+            if *times > 0 {
+                write!(syscalls, " {}*{}", id, times).unwrap();
+            }
+        }
+        println!(
+            "[kernel] pid {} exited: user {} us, kernel {} us, {} switches, syscalls:{}",
+            pid, self.info.user_time_us, self.info.kernel_time_us, self.info.switches, syscalls
+        );
+    }
+}
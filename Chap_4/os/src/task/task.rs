@@ -1,57 +1,247 @@
-use crate::task::context::TaskContext;
+use super::TaskContext;
+use super::scheduler::DEFAULT_PRIORITY;
+use super::stats::TaskStats;
+use super::{pid_alloc, KernelStack, PidHandle};
+use crate::config::{TRAP_CONTEXT, USER_SPACE_END};
+use crate::fs::{File, Stderr, Stdin, Stdout};
+use crate::mm::{ExecError, MemorySet, OutOfMemory, PhysPageNum, VirtAddr, KERNEL_SPACE};
+use crate::sync::UPSafeCell;
+use crate::trap::{trap_handler, TrapContext};
+use alloc::string::String;
+use alloc::sync::{Arc, Weak};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cell::RefMut;
 
 // 为这个类型提供一些Trait的默认实现
 #[derive(Copy, Clone, PartialEq)]     
 pub enum TaskStatus {
-    UnInit,
     Ready,
     Running,
-    Exited,
+    Blocked,      // 在定时器队列中睡眠，不在就绪队列中
+    Zombie,       // 进程已退出，但还没有被父进程回收
 }
 
-#[derive(Copy, Clone)]
 pub struct TaskControlBlock {
-    pub task_status: TaskStatus,
-    pub task_cx: TaskContext,           // 维护任务上下文
-    pub memory_set: MemorySet,
+    // 初始化之后就不再变化的元数据
+    pub pid: PidHandle,
+    pub kernel_stack: KernelStack,
+    // 运行过程中可能发生变化的元数据
+    inner: UPSafeCell<TaskControlBlockInner>,
+}
+
+pub struct TaskControlBlockInner {
     pub trap_cx_ppn: PhysPageNum,
     pub base_size: usize,
+    pub task_cx: TaskContext,           // 维护任务上下文
+    pub task_status: TaskStatus,
+    pub memory_set: MemorySet,
+    pub parent: Option<Weak<TaskControlBlock>>,     // 用Weak避免父子进程之间的循环引用
+    pub children: Vec<Arc<TaskControlBlock>>,
+    pub exit_code: i32,
+    pub fd_table: Vec<Option<Arc<dyn File>>>,     // 文件描述符表，下标即文件描述符，None表示空闲
+    pub cwd: String,                              // 当前工作目录的绝对路径
+    pub heap_bottom: usize,                       // 堆的起始地址
+    pub program_brk: usize,                       // 堆的结束地址，即program break
+    pub killed: bool,                             // 被OOM killer选中，下次返回用户态之前退出
+    pub priority: usize,                          // 调度优先级，不小于2
+    pub pass: usize,                              // Stride调度中已经走过的距离
+    pub queue_level: usize,                       // 在MLFQ中所处的队列
+    pub preempted: bool,                          // 上一次是因为用完时间片而被时钟中断抢占的
+    pub stats: TaskStats,                         // 运行情况的统计，子进程从头开始统计
+}
+
+impl TaskControlBlockInner {
+    pub fn get_trap_cx(&self) -> &'static mut TrapContext {
+        self.trap_cx_ppn.get_mut()
+    }
+    pub fn get_user_token(&self) -> usize {
+        self.memory_set.token()
+    }
+    fn get_status(&self) -> TaskStatus {
+        self.task_status
+    }
+    pub fn is_zombie(&self) -> bool {
+        self.get_status() == TaskStatus::Zombie
+    }
+    /// 分配一个最小的空闲文件描述符
+    pub fn alloc_fd(&mut self) -> usize {
+        if let Some(fd) = (0..self.fd_table.len()).find(|fd| self.fd_table[*fd].is_none()) {
+            fd
+        } else {
+            self.fd_table.push(None);
+            self.fd_table.len() - 1
+        }
+    }
 }
 
 impl TaskControlBlock{
-    pub fn new(elf_data: &[u8], app_id: usize) -> Self {
-        let (memory_set, user_sp, entry_point) = MemorySet::from_elf(elf_data);
+    pub fn inner_exclusive_access(&self) -> RefMut<'_, TaskControlBlockInner> {
+        self.inner.exclusive_access()
+    }
+    pub fn getpid(&self) -> usize {
+        self.pid.0
+    }
+    /// 只用于创建initproc，这时物理内存不可能不足
+    pub fn new(elf_data: &[u8]) -> Self {
+        let (memory_set, user_sp, entry_point) = MemorySet::from_elf(elf_data).unwrap();
         let trap_cx_ppn = memory_set
-            .translate(VirtApp::from(TRAP_CONTEXT).into())
+            .translate(VirtAddr::from(TRAP_CONTEXT).into())
             .unwrap()
             .ppn();
-        let task_status = TaskStatus::Ready;
-        let (kernel_stack_bottom, kernel_stack_top) = kernel_stack_position(app_id);
-        KERNEL_SPACE
-            .exclussive_access()
-            .insert_framed_area{
-                kernel_stack_bottom.into(),
-                kernel_stack_top.into(),
-                MapPermission::R | MapPermission::M,
-            };
+        // 分配pid和内核栈，二者都会在进程控制块被回收时自动释放
+        let pid_handle = pid_alloc();
+        let kernel_stack = KernelStack::new(&pid_handle).unwrap();
+        let kernel_stack_top = kernel_stack.get_top();
         let task_control_block = Self {
-            task_status,
-            task_cx: TaskContext::goto_trap_return(kernel_stack_top),
-            memory_set,
-            trap_cx_ppn,
-            base_size: user_sp,
+            pid: pid_handle,
+            kernel_stack,
+            inner: unsafe {
+                UPSafeCell::new(TaskControlBlockInner {
+                    trap_cx_ppn,
+                    base_size: user_sp,
+                    task_cx: TaskContext::goto_trap_return(kernel_stack_top),
+                    task_status: TaskStatus::Ready,
+                    memory_set,
+                    parent: None,
+                    children: Vec::new(),
+                    exit_code: 0,
+                    fd_table: vec![
+                        // 0 -> stdin
+                        Some(Arc::new(Stdin)),
+                        // 1 -> stdout
+                        Some(Arc::new(Stdout)),
+                        // 2 -> stderr
+                        Some(Arc::new(Stderr)),
+                    ],
+                    cwd: String::from("/"),
+                    heap_bottom: user_sp,
+                    program_brk: user_sp,
+                    killed: false,
+                    priority: DEFAULT_PRIORITY,
+                    pass: 0,
+                    queue_level: 0,
+                    preempted: false,
+                    stats: TaskStats::new(),
+                })
+            },
         };
-        let trap_cx = task_control_block.goto_trap_cx();
+        let trap_cx = task_control_block.inner_exclusive_access().get_trap_cx();
         *trap_cx = TrapContext::app_init_context(
             entry_point,
             user_sp,
-            KERNEL_SPACE.exclussive_access().token(),
+            KERNEL_SPACE.exclusive_access().token(),
             kernel_stack_top,
             trap_handler as usize,
         );
         task_control_block
     }
-    pub fn get_trap_cx(&self) -> &'static mut TrapContext {
-        self.trap_cx_ppn.get_mut()
+    /// 用新的ELF替换当前进程的地址空间，pid、内核栈和父子关系保持不变。
+    /// 不是合法的ELF文件或者物理内存不足时返回Err，原来的地址空间不受影响
+    pub fn exec(&self, elf_data: &[u8]) -> Result<(), ExecError> {
+        let (memory_set, user_sp, entry_point) = MemorySet::from_elf(elf_data)?;
+        let trap_cx_ppn = memory_set
+            .translate(VirtAddr::from(TRAP_CONTEXT).into())
+            .unwrap()
+            .ppn();
+        let mut inner = self.inner_exclusive_access();
+        inner.memory_set.sync_all();
+        // 原地址空间在这里被drop，物理页帧随之回收
+        inner.memory_set = memory_set;
+        inner.trap_cx_ppn = trap_cx_ppn;
+        inner.base_size = user_sp;
+        inner.heap_bottom = user_sp;
+        inner.program_brk = user_sp;
+        let trap_cx = inner.get_trap_cx();
+        *trap_cx = TrapContext::app_init_context(
+            entry_point,
+            user_sp,
+            KERNEL_SPACE.exclusive_access().token(),
+            self.kernel_stack.get_top(),
+            trap_handler as usize,
+        );
+        Ok(())
     }
-}
\ No newline at end of file
+    /// 复制当前进程得到一个子进程，子进程除了pid和内核栈外与父进程完全相同。
+    /// 物理内存不足时返回Err，这时已经分配的资源都会被回收
+    pub fn fork(self: &Arc<TaskControlBlock>) -> Result<Arc<TaskControlBlock>, OutOfMemory> {
+        let mut parent_inner = self.inner_exclusive_access();
+        let memory_set = MemorySet::from_existed_user(&mut parent_inner.memory_set)?;
+        let trap_cx_ppn = memory_set
+            .translate(VirtAddr::from(TRAP_CONTEXT).into())
+            .unwrap()
+            .ppn();
+        let pid_handle = pid_alloc();
+        let kernel_stack = KernelStack::new(&pid_handle)?;
+        let kernel_stack_top = kernel_stack.get_top();
+        // 子进程继承父进程打开的所有文件
+        let mut new_fd_table: Vec<Option<Arc<dyn File>>> = Vec::new();
+        for fd in parent_inner.fd_table.iter() {
+            if let Some(file) = fd {
+                new_fd_table.push(Some(file.clone()));
+            } else {
+                new_fd_table.push(None);
+            }
+        }
+        let task_control_block = Arc::new(TaskControlBlock {
+            pid: pid_handle,
+            kernel_stack,
+            inner: unsafe {
+                UPSafeCell::new(TaskControlBlockInner {
+                    trap_cx_ppn,
+                    base_size: parent_inner.base_size,
+                    task_cx: TaskContext::goto_trap_return(kernel_stack_top),
+                    task_status: TaskStatus::Ready,
+                    memory_set,
+                    parent: Some(Arc::downgrade(self)),
+                    children: Vec::new(),
+                    exit_code: 0,
+                    fd_table: new_fd_table,
+                    cwd: parent_inner.cwd.clone(),
+                    heap_bottom: parent_inner.heap_bottom,
+                    program_brk: parent_inner.program_brk,
+                    killed: false,
+                    // 子进程继承父进程的优先级，并从父进程当前的pass开始，不会因为pass太小而长期霸占CPU
+                    priority: parent_inner.priority,
+                    pass: parent_inner.pass,
+                    // 新进程总是从最高优先级的队列开始
+                    queue_level: 0,
+                    preempted: false,
+                    stats: TaskStats::new(),
+                })
+            },
+        });
+        parent_inner.children.push(task_control_block.clone());
+        // trap上下文已经随地址空间一起拷贝过来了，只需要修改其中的内核栈指针
+        let trap_cx = task_control_block.inner_exclusive_access().get_trap_cx();
+        trap_cx.kernel_sp = kernel_stack_top;
+        Ok(task_control_block)
+    }
+    /// 把program break移动size字节，返回原来的program break；
+    /// 不能缩到堆的起始地址以下，也不能超出用户地址空间或者和其他逻辑段重叠
+    pub fn change_program_brk(&self, size: i32) -> Option<usize> {
+        let mut inner = self.inner_exclusive_access();
+        let heap_bottom = inner.heap_bottom;
+        let old_brk = inner.program_brk;
+        let new_brk = old_brk as isize + size as isize;
+        if new_brk < heap_bottom as isize || new_brk as usize > USER_SPACE_END {
+            return None;
+        }
+        let new_brk = new_brk as usize;
+        let result = if size < 0 {
+            inner
+                .memory_set
+                .shrink_to(VirtAddr(heap_bottom), VirtAddr(new_brk))
+        } else {
+            inner
+                .memory_set
+                .append_to(VirtAddr(heap_bottom), VirtAddr(new_brk))
+        };
+        if result {
+            inner.program_brk = new_brk;
+            Some(old_brk)
+        } else {
+            None
+        }
+    }
+}
@@ -1,9 +1,37 @@
 use crate::config::CLOCK_FREQ;
 use crate::sbi::set_timer;
+use crate::sync::UPSafeCell;
+use crate::task::{wakeup_task, TaskControlBlock};
+use alloc::collections::BinaryHeap;
+use alloc::sync::Arc;
+use core::cmp::Ordering;
+use lazy_static::*;
 use riscv::register::time;
 
 const TICKS_PER_SEC: usize = 100;
 const MSEC_PER_SEC: usize = 1000;
+const NSEC_PER_MSEC: usize = 1_000_000;
+
+/// nanosleep的参数，内存布局与Linux和用户库中的定义一致
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct TimeSpec {
+    pub tv_sec: usize,
+    pub tv_nsec: usize,
+}
+
+impl TimeSpec {
+    /// tv_nsec必须小于1秒
+    pub fn is_valid(&self) -> bool {
+        self.tv_nsec < MSEC_PER_SEC * NSEC_PER_MSEC
+    }
+    /// 换算成毫秒，不足1毫秒的部分向上取整，溢出时取usize::MAX
+    pub fn as_ms(&self) -> usize {
+        self.tv_sec
+            .saturating_mul(MSEC_PER_SEC)
+            .saturating_add((self.tv_nsec + NSEC_PER_MSEC - 1) / NSEC_PER_MSEC)
+    }
+}
 
 pub fn get_time() -> usize {
     time::read()
@@ -23,3 +51,56 @@ pub fn get_time_us() -> usize {
 pub fn get_time_ms() -> usize {
     time::read() / (CLOCK_FREQ / MSEC_PER_SEC)
 }
+
+/// 定时器队列中的一项：到expire_ms时唤醒task
+pub struct TimerCondVar {
+    pub expire_ms: usize,
+    pub task: Arc<TaskControlBlock>,
+}
+
+impl PartialEq for TimerCondVar {
+    fn eq(&self, other: &Self) -> bool {
+        self.expire_ms == other.expire_ms
+    }
+}
+
+impl Eq for TimerCondVar {}
+
+impl PartialOrd for TimerCondVar {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for TimerCondVar {
+    /// BinaryHeap是大根堆，这里反过来比较，让最早到期的在堆顶
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.expire_ms.cmp(&self.expire_ms)
+    }
+}
+
+lazy_static! {
+    /// 按唤醒时间排序的睡眠进程
+    static ref TIMERS: UPSafeCell<BinaryHeap<TimerCondVar>> =
+        unsafe { UPSafeCell::new(BinaryHeap::new()) };
+}
+
+/// 在expire_ms时唤醒task，task随后应当被阻塞
+pub fn add_timer(expire_ms: usize, task: Arc<TaskControlBlock>) {
+    TIMERS
+        .exclusive_access()
+        .push(TimerCondVar { expire_ms, task });
+}
+
+/// 唤醒所有已经到期的进程，在时钟中断和idle控制流中调用
+pub fn check_timer() {
+    let now = get_time_ms();
+    let mut timers = TIMERS.exclusive_access();
+    while let Some(timer) = timers.peek() {
+        if timer.expire_ms > now {
+            break;
+        }
+        let task = timers.pop().unwrap().task;
+        wakeup_task(task);
+    }
+}
@@ -1,9 +1,14 @@
 mod context;
 
+use crate::config::{TRAMPOLINE, TRAP_CONTEXT};
+use crate::mm::Access;
 use crate::syscall::syscall;
-use core::arch::global_asm;
-use crate::task::{exit_current_and_run_next, suspend_current_and_run_next};
-use crate::timer::set_next_trigger;
+use crate::task::{
+    account_trap_enter, account_trap_return, current_killed, current_trap_cx, current_user_token,
+    exit_current_and_run_next, handle_page_fault, preempt_current_and_run_next,
+};
+use crate::timer::{check_timer, set_next_trigger};
+use core::arch::{asm, global_asm};
 use riscv::register::{
     mtvec::TrapMode,
     scause::{self, Exception, Trap, Interrupt},
@@ -12,16 +17,13 @@ use riscv::register::{
 
 global_asm!(include_str!("trap.S"));
 
-// 通过 __alltraps将Trap上下文保存在内核栈上， 
-// 跳转到trap_handler函数完成Trap分发与处理
-// trap_handler返回后，用__restore从保存在内核栈上的Trap上下文恢复寄存器
+// 通过 __alltraps将Trap上下文保存在应用地址空间的TRAP_CONTEXT页上，
+// 切换到内核地址空间后跳转到trap_handler函数完成Trap分发与处理
+// trap_handler处理完后调用trap_return，通过跳板页上的__restore恢复寄存器
 // 最后通过sret回到应用程序
 
 pub fn init(){
-    extern "C" {fn __alltraps(); }     // 使用alltrap将Trap上下文保存在内核栈上
-    unsafe {
-        stvec::write(__alltraps as usize, TrapMode::Direct);    // 在初始化时就将__alltraps设置为trap的入口！！
-    }
+    set_kernel_trap_entry();
 }
 
 /// timer interrupt enabled
@@ -37,6 +39,12 @@ fn set_kernel_trap_entry(){
     }
 }
 
+fn set_user_trap_entry() {
+    unsafe {
+        stvec::write(TRAMPOLINE as usize, TrapMode::Direct);     // __alltraps在跳板页上的虚拟地址
+    }
+}
+
 #[no_mangle]
 pub fn trap_from_kernel() -> ! {
     panic!("a trap from kernel!");
@@ -45,26 +53,47 @@ pub fn trap_from_kernel() -> ! {
 #[no_mangle]
 pub fn trap_handler() -> !{
     set_kernel_trap_entry();
-    let cx = current_trap_cx();
+    account_trap_enter();
     let scause = scause::read();
     let stval = stval::read();
     match scause.cause() {    // 对trap的原因进行分发处理
         Trap::Exception(Exception::UserEnvCall) => {
+            let mut cx = current_trap_cx();
             cx.sepc += 4;
-            cx.x[10] = syscall(cx.x[17], [cx.x[10], cx.x[11], cx.x[12]]) as usize;
+            let result = syscall(
+                cx.x[17],
+                [cx.x[10], cx.x[11], cx.x[12], cx.x[13], cx.x[14], cx.x[15]],
+            );
+            // sys_exec会替换掉当前进程的地址空间，trap上下文所在的物理页帧也随之改变，需要重新获取
+            cx = current_trap_cx();
+            cx.x[10] = result as usize;
         }
+        // 按需分配的页面、换出的页面和写时复制的页面在这里处理，之后回到用户态重新执行这条指令
+        Trap::Exception(Exception::LoadPageFault) if handle_page_fault(stval, Access::Read) => {}
+        Trap::Exception(Exception::StorePageFault) if handle_page_fault(stval, Access::Write) => {}
+        Trap::Exception(Exception::InstructionPageFault) if handle_page_fault(stval, Access::Exec) => {}
         Trap::Exception(Exception::StoreFault) | 
-        Trap::Exception(Exception::StorePageFault) => {
-            println!("[kernel] PageFault in application, kernel killed it.");
-            exit_current_and_run_next();
+        Trap::Exception(Exception::StorePageFault) |
+        Trap::Exception(Exception::InstructionFault) |
+        Trap::Exception(Exception::InstructionPageFault) |
+        Trap::Exception(Exception::LoadFault) |
+        Trap::Exception(Exception::LoadPageFault) => {
+            println!(
+                "[kernel] {:?} in application, bad addr = {:#x} (outside any mapped area or not permitted), bad instruction = {:#x}, kernel killed it.",
+                scause.cause(),
+                stval,
+                current_trap_cx().sepc,
+            );
+            exit_current_and_run_next(-2);
         }
         Trap::Exception(Exception::IllegalInstruction) => {
             println!("[kernel] IllegalInstruction in application, kernel killed it.");
-            exit_current_and_run_next();
+            exit_current_and_run_next(-3);
         }
         Trap::Interrupt(Interrupt::SupervisorTimer) => {
             set_next_trigger();
-            suspend_current_and_run_next();
+            check_timer();
+            preempt_current_and_run_next();
         }
         _ => {
             panic!("Unsupported trap {:?}, stval = {:#x}!", scause.cause(), stval);
@@ -75,6 +104,11 @@ pub fn trap_handler() -> !{
 
 #[no_mangle]
 pub fn trap_return() -> ! {
+    // 被OOM killer选中的进程不再回到用户态
+    if current_killed() {
+        exit_current_and_run_next(-9);
+    }
+    account_trap_return();
     set_user_trap_entry();
     let trap_cx_ptr = TRAP_CONTEXT;
     let user_satp = current_user_token();
@@ -85,15 +119,14 @@ pub fn trap_return() -> ! {
     let restore_va = __restore as usize - __alltraps as usize + TRAMPOLINE;
     unsafe {
         asm!(
-            "fence.i"
-            "jr {restore_va}"
+            "fence.i",
+            "jr {restore_va}",
             restore_va = in(reg) restore_va,
-            in ("a0") trap_cx_ptr,
-            in ("a1") user_satp,
+            in("a0") trap_cx_ptr,
+            in("a1") user_satp,
             options(noreturn)
         );
     }
-    panic!("Unreachable in back_to_user!");
 }
 
-pub use context::TrapContext;
\ No newline at end of file
+pub use context::TrapContext;
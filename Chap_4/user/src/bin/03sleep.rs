@@ -4,15 +4,28 @@
 #[macro_use]
 extern crate user_lib;
 
-use user_lib::{get_time, yield_};
+use user_lib::{get_time, getpid, nanosleep, sleep, task_info, TaskInfo, TimeSpec};
+
+const SLEEP_MS: isize = 3000;
+const EINVAL: isize = 22;
 
 #[no_mangle]
 fn main() -> i32 {
     let current_timer = get_time();
-    let wait_for = current_timer + 3000;
-    while get_time() < wait_for {
-        yield_();
-    }
+    sleep(SLEEP_MS as usize);
+    let elapsed = get_time() - current_timer;
+    assert!(elapsed >= SLEEP_MS);
+    // 睡眠期间不在就绪队列中，用户态和内核态时间都不应该算上睡眠的时间
+    let mut info = TaskInfo::new();
+    assert_eq!(task_info(getpid() as usize, &mut info), 0);
+    assert!(info.user_time_us + info.kernel_time_us < SLEEP_MS as usize * 1000 / 10);
+    // tv_nsec必须小于1秒
+    let req = TimeSpec {
+        tv_sec: 0,
+        tv_nsec: 1_000_000_000,
+    };
+    assert_eq!(nanosleep(&req), -EINVAL);
+    println!("slept {} ms", elapsed);
     println!("Test sleep OK!");
     0
 }
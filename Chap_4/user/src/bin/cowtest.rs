@@ -0,0 +1,60 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{exit, fork, waitpid};
+
+const PAGE_SIZE: usize = 4096;
+/// 1MiB的数据，8个子进程如果各自完整拷贝一份，会远远超出内核可用的物理内存
+const DATA_SIZE: usize = 1 << 20;
+const CHILDREN: usize = 8;
+
+static mut DATA: [u8; DATA_SIZE] = [0; DATA_SIZE];
+static mut EXIT_CODE: i32 = 0;
+
+fn pattern(i: usize) -> u8 {
+    (i / PAGE_SIZE) as u8 ^ 0x5a
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    unsafe {
+        for i in (0..DATA_SIZE).step_by(PAGE_SIZE) {
+            DATA[i] = pattern(i);
+        }
+    }
+    let mut pids = [0usize; CHILDREN];
+    for (id, pid) in pids.iter_mut().enumerate() {
+        let ret = fork();
+        if ret == 0 {
+            unsafe {
+                // 子进程先看到和父进程相同的数据
+                for i in (0..DATA_SIZE).step_by(PAGE_SIZE) {
+                    assert_eq!(DATA[i], pattern(i));
+                }
+                // 写入只影响自己
+                let page = id * PAGE_SIZE;
+                DATA[page] = 0xff;
+                assert_eq!(DATA[page], 0xff);
+            }
+            exit(100 + id as i32);
+        }
+        assert!(ret > 0);
+        *pid = ret as usize;
+    }
+    for (id, pid) in pids.iter().enumerate() {
+        // 退出码由内核写入与子进程共享的页面，不能影响到子进程看到的数据
+        let exit_code = unsafe { &mut *core::ptr::addr_of_mut!(EXIT_CODE) };
+        assert_eq!(waitpid(*pid, exit_code), *pid as isize);
+        assert_eq!(*exit_code, 100 + id as i32);
+    }
+    unsafe {
+        for i in (0..DATA_SIZE).step_by(PAGE_SIZE) {
+            assert_eq!(DATA[i], pattern(i));
+        }
+    }
+    println!("cowtest passed!");
+    0
+}
@@ -0,0 +1,63 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{chdir, close, getcwd, mkdir, open, read, rmdir, unlink, write, OpenFlags};
+
+const ENOENT: isize = 2;
+const EEXIST: isize = 17;
+const ENOTDIR: isize = 20;
+const EISDIR: isize = 21;
+const ENOTEMPTY: isize = 39;
+
+fn assert_cwd(expected: &str) {
+    let mut buf = [0u8; 64];
+    let len = getcwd(&mut buf);
+    assert!(len > 0);
+    assert_eq!(core::str::from_utf8(&buf[..len as usize]).unwrap(), expected);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_cwd("/");
+    assert_eq!(mkdir("dir_a"), 0);
+    assert_eq!(mkdir("dir_a"), -EEXIST);
+    assert_eq!(mkdir("dir_a/dir_b"), 0);
+    assert_eq!(mkdir("no_such_dir/dir_c"), -ENOENT);
+
+    // 多级路径以及"."和".."
+    let fd = open("/dir_a/./dir_b/../dir_b/file", OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    assert_eq!(write(fd as usize, b"nested"), 6);
+    close(fd as usize);
+    assert_eq!(mkdir("/dir_a/dir_b/file/dir_d"), -ENOTDIR);
+
+    // 切换工作目录后相对路径从新的目录出发
+    assert_eq!(chdir("dir_a/dir_b"), 0);
+    assert_cwd("/dir_a/dir_b");
+    let fd = open("file", OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let mut buf = [0u8; 16];
+    assert_eq!(read(fd as usize, &mut buf), 6);
+    assert_eq!(&buf[..6], b"nested");
+    close(fd as usize);
+    assert_eq!(chdir("file"), -ENOTDIR);
+    assert_eq!(chdir(".."), 0);
+    assert_cwd("/dir_a");
+    assert_eq!(chdir("../.."), 0);
+    assert_cwd("/");
+
+    // 目录只能用rmdir删除，并且必须是空的
+    assert_eq!(open("dir_a", OpenFlags::WRONLY), -EISDIR);
+    assert_eq!(unlink("dir_a"), -EISDIR);
+    assert_eq!(rmdir("dir_a"), -ENOTEMPTY);
+    assert_eq!(rmdir("dir_a/dir_b/file"), -ENOTDIR);
+    assert_eq!(unlink("dir_a/dir_b/file"), 0);
+    assert_eq!(rmdir("dir_a/dir_b"), 0);
+    assert_eq!(rmdir("dir_a"), 0);
+    assert_eq!(chdir("dir_a"), -ENOENT);
+    println!("dirtest passed!");
+    0
+}
@@ -0,0 +1,58 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, exit, fork, fstat, mmap, open, pipe, read, waitpid, write, OpenFlags, Stat, PROT_READ,
+};
+
+const PAGE_SIZE: usize = 4096;
+const EFAULT: isize = 14;
+/// 程序从0x10000开始加载，它下面的页面没有映射
+const UNMAPPED: usize = 0x1000;
+const READ_ONLY: usize = 0x1000_0000;
+/// 截断成39位之后正好是程序的代码段，内核不能把它当成用户地址
+const ALIASED: usize = (1 << 39) + 0x10000;
+
+fn bad_buf(addr: usize, len: usize) -> &'static mut [u8] {
+    unsafe { core::slice::from_raw_parts_mut(addr as *mut u8, len) }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // 指针指向没有映射的页面时系统调用返回-EFAULT，而不是访问到其他物理内存
+    assert_eq!(write(1, bad_buf(UNMAPPED, 16)), -EFAULT);
+    assert_eq!(write(1, bad_buf(ALIASED, 16)), -EFAULT);
+    let fd = open("efault", OpenFlags::CREATE | OpenFlags::TRUNC | OpenFlags::RDWR);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    assert_eq!(write(fd, b"hello"), 5);
+    close(fd);
+    let fd = open("efault", OpenFlags::RDONLY) as usize;
+    assert_eq!(read(fd, bad_buf(UNMAPPED, 16)), -EFAULT);
+    let st = unsafe { &mut *(UNMAPPED as *mut Stat) };
+    assert_eq!(fstat(fd, st), -EFAULT);
+
+    // 只读的页面同样不能被内核代替用户程序写入
+    assert_eq!(mmap(READ_ONLY, PAGE_SIZE, PROT_READ), READ_ONLY as isize);
+    assert_eq!(read(fd, bad_buf(READ_ONLY, 16)), -EFAULT);
+    close(fd);
+
+    let pipe_fd = unsafe { core::slice::from_raw_parts_mut(UNMAPPED as *mut usize, 2) };
+    assert_eq!(pipe(pipe_fd), -EFAULT);
+
+    // 写回退出码失败时子进程留给下一次waitpid回收
+    let pid = fork();
+    if pid == 0 {
+        exit(7);
+    }
+    let exit_code = unsafe { &mut *(UNMAPPED as *mut i32) };
+    assert_eq!(waitpid(pid as usize, exit_code), -EFAULT);
+    let mut exit_code: i32 = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 7);
+    println!("efaulttest passed!");
+    0
+}
@@ -0,0 +1,30 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, dup, read, write};
+
+const STDIN: usize = 0;
+const STDOUT: usize = 1;
+const EBADF: isize = 9;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fd = dup(STDOUT);
+    assert!(fd > 2);
+    let msg = "write through a duplicated stdout\n";
+    assert_eq!(write(fd as usize, msg.as_bytes()), msg.len() as isize);
+    assert_eq!(close(fd as usize), 0);
+    // 关闭之后再访问，以及访问从未打开过的描述符，都应返回-EBADF
+    assert_eq!(write(fd as usize, msg.as_bytes()), -EBADF);
+    assert_eq!(close(fd as usize), -EBADF);
+    assert_eq!(dup(100), -EBADF);
+    // 方向不对的访问同样返回-EBADF
+    let mut buf = [0u8; 1];
+    assert_eq!(read(STDOUT, &mut buf), -EBADF);
+    assert_eq!(write(STDIN, &buf), -EBADF);
+    println!("fdtest passed!");
+    0
+}
@@ -0,0 +1,66 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, exec, open, read, write, OpenFlags};
+
+const ENOENT: isize = 2;
+const ENOEXEC: isize = 8;
+
+/// 读出文件的全部内容，返回读到的字节数
+fn read_all(path: &str, buf: &mut [u8]) -> usize {
+    let fd = open(path, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    let mut total = 0;
+    loop {
+        let len = read(fd, &mut buf[total..]);
+        assert!(len >= 0);
+        if len == 0 {
+            break;
+        }
+        total += len as usize;
+    }
+    close(fd);
+    total
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let path = "filea";
+    let greet = "Hello, world!";
+    // 创建文件并写入
+    let fd = open(path, OpenFlags::CREATE | OpenFlags::TRUNC | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    assert_eq!(write(fd, greet.as_bytes()), greet.len() as isize);
+    // 只写打开的文件不能读
+    let mut buf = [0u8; 100];
+    assert!(read(fd, &mut buf) < 0);
+    close(fd);
+
+    // 重新打开，读出的内容应当和写入的一致
+    let len = read_all(path, &mut buf);
+    assert_eq!(core::str::from_utf8(&buf[..len]).unwrap(), greet);
+
+    // 追加写入
+    let fd = open(path, OpenFlags::WRONLY | OpenFlags::APPEND) as usize;
+    assert_eq!(write(fd, b" Again!"), 7);
+    close(fd);
+    let len = read_all(path, &mut buf);
+    assert_eq!(core::str::from_utf8(&buf[..len]).unwrap(), "Hello, world! Again!");
+    // 文本文件不是ELF，exec失败，当前进程照常运行
+    assert_eq!(exec(path), -ENOEXEC);
+
+    // 截断之后文件为空
+    let fd = open(path, OpenFlags::WRONLY | OpenFlags::TRUNC) as usize;
+    close(fd);
+    assert_eq!(read_all(path, &mut buf), 0);
+
+    // 不存在的文件且没有CREATE时打开失败
+    assert_eq!(open("no_such_file", OpenFlags::RDONLY), -ENOENT);
+    println!("filetest passed!");
+    0
+}
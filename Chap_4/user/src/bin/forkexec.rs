@@ -0,0 +1,28 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{exec, fork, getpid, waitpid};
+
+#[no_mangle]
+pub fn main() -> i32 {
+    println!("pid {}: parent start forking ...", getpid());
+    let pid = fork();
+    if pid == 0 {
+        // child process
+        println!("pid {}: forked child start execing", getpid());
+        exec("01power_5\0");
+        panic!("exec 01power_5 failed!");
+    } else {
+        // parent process
+        let mut exit_code: i32 = 0;
+        println!("pid {}: ready waiting child ...", getpid());
+        assert_eq!(pid, waitpid(pid as usize, &mut exit_code));
+        assert_eq!(exit_code, 0);
+        println!("pid {}: got child info:: pid {}, exit code: {}", getpid(), pid, exit_code);
+        println!("forkexec pass.");
+    }
+    0
+}
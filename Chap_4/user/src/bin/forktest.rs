@@ -0,0 +1,37 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{exit, fork, getpid, wait};
+
+const MAX_CHILD: usize = 8;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    for i in 0..MAX_CHILD {
+        let pid = fork();
+        if pid == 0 {
+            println!("I am child {}, pid = {}", i, getpid());
+            exit(i as i32 + 100);
+        } else {
+            println!("forked child pid = {}", pid);
+        }
+        assert!(pid > 0);
+    }
+    let mut exit_code: i32 = 0;
+    let mut exit_sum: i32 = 0;
+    for _ in 0..MAX_CHILD {
+        if wait(&mut exit_code) <= 0 {
+            panic!("wait stopped early");
+        }
+        exit_sum += exit_code;
+    }
+    if wait(&mut exit_code) > 0 {
+        panic!("wait got too many");
+    }
+    assert_eq!(exit_sum, (100..100 + MAX_CHILD as i32).sum());
+    println!("forktest pass.");
+    0
+}
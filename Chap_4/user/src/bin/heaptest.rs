@@ -0,0 +1,53 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use user_lib::sbrk;
+
+const PAGE_SIZE: usize = 4096;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // 直接使用sbrk：扩大、写入、再缩回去
+    let brk = sbrk(0);
+    assert!(brk > 0);
+    assert_eq!(sbrk(2 * PAGE_SIZE as i32), brk);
+    let new_brk = sbrk(0);
+    assert_eq!(new_brk, brk + 2 * PAGE_SIZE as isize);
+    unsafe {
+        let p = brk as usize as *mut u8;
+        p.write_volatile(0x5a);
+        p.add(2 * PAGE_SIZE - 1).write_volatile(0xa5);
+        assert_eq!(p.read_volatile(), 0x5a);
+        assert_eq!(p.add(2 * PAGE_SIZE - 1).read_volatile(), 0xa5);
+    }
+    assert_eq!(sbrk(-2 * PAGE_SIZE as i32), new_brk);
+    assert_eq!(sbrk(0), brk);
+    // 不能缩到堆的起始地址以下
+    assert_eq!(sbrk(-PAGE_SIZE as i32), -1);
+    assert_eq!(sbrk(0), brk);
+
+    // 通过全局分配器使用堆
+    let mut v: Vec<usize> = Vec::new();
+    for i in 0..20000 {
+        v.push(i);
+    }
+    for (i, x) in v.iter().enumerate() {
+        assert_eq!(*x, i);
+    }
+    let mut s = String::new();
+    for i in 0..100 {
+        s.push_str(if i % 2 == 0 { "ab" } else { "c" });
+    }
+    assert_eq!(s.len(), 150);
+    drop(v);
+    assert!(sbrk(0) > brk);
+    println!("heap grew by {} bytes", sbrk(0) - brk);
+    println!("heaptest passed!");
+    0
+}
@@ -0,0 +1,46 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::run_in_child;
+
+const PAGE_SIZE: usize = 4096;
+/// 64MiB的.bss，远远超出QEMU上的物理内存，只有按需分配时程序才能运行
+const BSS_SIZE: usize = 64 << 20;
+/// 实际访问的页面数
+const TOUCHED_PAGES: usize = 16;
+
+static mut BSS: [u8; BSS_SIZE] = [0; BSS_SIZE];
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let stride = BSS_SIZE / TOUCHED_PAGES;
+    unsafe {
+        let bss = core::ptr::addr_of_mut!(BSS) as *mut u8;
+        // 没有写过的页面读出来是0
+        for i in 0..TOUCHED_PAGES {
+            assert_eq!(bss.add(i * stride + PAGE_SIZE / 2).read_volatile(), 0);
+        }
+        for i in 0..TOUCHED_PAGES {
+            bss.add(i * stride).write_volatile(i as u8 + 1);
+        }
+        for i in 0..TOUCHED_PAGES {
+            assert_eq!(bss.add(i * stride).read_volatile(), i as u8 + 1);
+        }
+    }
+    println!("touched {} pages of a {} MiB bss", TOUCHED_PAGES, BSS_SIZE >> 20);
+    // 不在任何逻辑段中的地址
+    let exit_code = run_in_child(|| unsafe {
+        (PAGE_SIZE as *const u8).read_volatile();
+    });
+    assert_eq!(exit_code, -2);
+    // 写只读的代码段
+    let exit_code = run_in_child(|| unsafe {
+        (main as usize as *mut u8).write_volatile(0);
+    });
+    assert_eq!(exit_code, -2);
+    println!("lazytest passed!");
+    0
+}
@@ -0,0 +1,65 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, fstat, link, mkdir, open, read, rmdir, unlink, write, OpenFlags, Stat, SEEK_SET,
+};
+
+const ENOENT: isize = 2;
+const EPERM: isize = 1;
+const EEXIST: isize = 17;
+
+fn nlink(fd: usize) -> u32 {
+    let mut stat = Stat::new();
+    assert_eq!(fstat(fd, &mut stat), 0);
+    stat.nlink
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fd = open("link_src", OpenFlags::CREATE | OpenFlags::TRUNC | OpenFlags::RDWR);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    assert_eq!(write(fd, b"hard link"), 9);
+    assert_eq!(nlink(fd), 1);
+
+    // 两个名字指向同一个索引节点
+    assert_eq!(link("link_src", "link_dst"), 0);
+    assert_eq!(link("link_src", "link_dst"), -EEXIST);
+    assert_eq!(link("no_such_file", "link_dst2"), -ENOENT);
+    assert_eq!(nlink(fd), 2);
+    let fd2 = open("link_dst", OpenFlags::RDONLY);
+    assert!(fd2 > 0);
+    let fd2 = fd2 as usize;
+    let mut stat = Stat::new();
+    let mut stat2 = Stat::new();
+    fstat(fd, &mut stat);
+    fstat(fd2, &mut stat2);
+    assert_eq!(stat.ino, stat2.ino);
+    let mut buf = [0u8; 16];
+    assert_eq!(read(fd2, &mut buf), 9);
+    assert_eq!(&buf[..9], b"hard link");
+    close(fd2);
+
+    // 不能为目录创建硬链接
+    assert_eq!(mkdir("link_dir"), 0);
+    assert_eq!(link("link_dir", "link_dir2"), -EPERM);
+    assert_eq!(rmdir("link_dir"), 0);
+
+    // 删除所有链接之后，已经打开的文件仍然可以读写，关闭后才真正被回收
+    assert_eq!(unlink("link_src"), 0);
+    assert_eq!(nlink(fd), 1);
+    assert_eq!(unlink("link_dst"), 0);
+    assert_eq!(nlink(fd), 0);
+    assert_eq!(open("link_dst", OpenFlags::RDONLY), -ENOENT);
+    assert_eq!(write(fd, b"!"), 1);
+    assert_eq!(user_lib::lseek(fd, 0, SEEK_SET), 0);
+    assert_eq!(read(fd, &mut buf), 10);
+    assert_eq!(&buf[..10], b"hard link!");
+    close(fd);
+    println!("linktest passed!");
+    0
+}
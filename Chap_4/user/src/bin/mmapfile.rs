@@ -0,0 +1,132 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, exit, fork, lseek, mmap_file, msync, munmap, open, read, waitpid, write, OpenFlags,
+    MAP_PRIVATE, MAP_SHARED, PROT_EXEC, PROT_READ, PROT_WRITE, SEEK_END, SEEK_SET,
+};
+
+const PAGE_SIZE: usize = 4096;
+/// 文件有两页多一点
+const FILE_SIZE: usize = 2 * PAGE_SIZE + 100;
+const SHARED: usize = 0x1000_0000;
+const PRIVATE: usize = 0x2000_0000;
+const CODE: usize = 0x3000_0000;
+/// `li a0, 42; ret`
+const CODE_BYTES: [u8; 8] = [0x13, 0x05, 0xa0, 0x02, 0x67, 0x80, 0x00, 0x00];
+
+fn pattern(i: usize) -> u8 {
+    (i % 251) as u8
+}
+
+fn at(base: usize, offset: usize) -> *mut u8 {
+    (base + offset) as *mut u8
+}
+
+/// 从文件offset处读一个字节
+fn read_byte(fd: usize, offset: usize) -> u8 {
+    let mut buf = [0u8; 1];
+    assert_eq!(lseek(fd, offset as isize, SEEK_SET), offset as isize);
+    assert_eq!(read(fd, &mut buf), 1);
+    buf[0]
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fd = open("mmapf", OpenFlags::CREATE | OpenFlags::TRUNC | OpenFlags::RDWR);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    let mut buf = [0u8; 256];
+    let mut written = 0;
+    while written < FILE_SIZE {
+        let len = buf.len().min(FILE_SIZE - written);
+        for (i, b) in buf[..len].iter_mut().enumerate() {
+            *b = pattern(written + i);
+        }
+        assert_eq!(write(fd, &buf[..len]), len as isize);
+        written += len;
+    }
+
+    // 页面在第一次访问时从文件读入，超出文件末尾的部分是0
+    let len = 3 * PAGE_SIZE;
+    assert_eq!(
+        mmap_file(SHARED, len, PROT_READ | PROT_WRITE, MAP_SHARED, fd, 0),
+        SHARED as isize
+    );
+    for i in (0..FILE_SIZE).step_by(97) {
+        assert_eq!(unsafe { at(SHARED, i).read_volatile() }, pattern(i));
+    }
+    assert_eq!(unsafe { at(SHARED, FILE_SIZE).read_volatile() }, 0);
+    // 偏移必须按页对齐
+    assert_eq!(mmap_file(PRIVATE, PAGE_SIZE, PROT_READ, MAP_PRIVATE, fd, 1), -1);
+
+    // MAP_SHARED的修改在msync之后写回文件
+    unsafe {
+        at(SHARED, 10).write_volatile(0xaa);
+        at(SHARED, PAGE_SIZE + 10).write_volatile(0xbb);
+    }
+    assert_eq!(msync(SHARED, len), 0);
+    assert_eq!(read_byte(fd, 10), 0xaa);
+    assert_eq!(read_byte(fd, PAGE_SIZE + 10), 0xbb);
+
+    // fork时已经读入的MAP_SHARED页面由父子进程共享
+    let pid = fork();
+    if pid == 0 {
+        unsafe {
+            at(SHARED, 20).write_volatile(0xcc);
+        }
+        exit(0);
+    }
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(unsafe { at(SHARED, 20).read_volatile() }, 0xcc);
+
+    // MAP_PRIVATE的修改只有自己可见，也不会写回文件
+    assert_eq!(
+        mmap_file(PRIVATE, PAGE_SIZE, PROT_READ | PROT_WRITE, MAP_PRIVATE, fd, PAGE_SIZE),
+        PRIVATE as isize
+    );
+    assert_eq!(unsafe { at(PRIVATE, 10).read_volatile() }, 0xbb);
+    unsafe {
+        at(PRIVATE, 10).write_volatile(0xdd);
+    }
+    assert_eq!(msync(PRIVATE, PAGE_SIZE), 0);
+    assert_eq!(munmap(PRIVATE, PAGE_SIZE), 0);
+    assert_eq!(read_byte(fd, PAGE_SIZE + 10), 0xbb);
+
+    // munmap时写回，文件不会因为映射超出末尾而变长
+    unsafe {
+        at(SHARED, 2 * PAGE_SIZE).write_volatile(0xee);
+        at(SHARED, FILE_SIZE + 1).write_volatile(0xff);
+    }
+    assert_eq!(munmap(SHARED, len), 0);
+    assert_eq!(read_byte(fd, 20), 0xcc);
+    assert_eq!(read_byte(fd, 2 * PAGE_SIZE), 0xee);
+    assert_eq!(lseek(fd, 0, SEEK_END), FILE_SIZE as isize);
+    close(fd);
+
+    // 只读打开的文件不能建立可写的共享映射
+    let fd = open("mmapf", OpenFlags::RDONLY) as usize;
+    assert_eq!(
+        mmap_file(SHARED, PAGE_SIZE, PROT_READ | PROT_WRITE, MAP_SHARED, fd, 0),
+        -1
+    );
+    close(fd);
+
+    // 可执行的文件映射在第一次取指时从文件读入
+    let fd = open("mmapx", OpenFlags::CREATE | OpenFlags::TRUNC | OpenFlags::RDWR) as usize;
+    assert_eq!(write(fd, &CODE_BYTES), CODE_BYTES.len() as isize);
+    assert_eq!(
+        mmap_file(CODE, PAGE_SIZE, PROT_READ | PROT_EXEC, MAP_PRIVATE, fd, 0),
+        CODE as isize
+    );
+    let f: fn() -> i32 = unsafe { core::mem::transmute(CODE) };
+    assert_eq!(f(), 42);
+    assert_eq!(munmap(CODE, PAGE_SIZE), 0);
+    close(fd);
+    println!("mmapfile passed!");
+    0
+}
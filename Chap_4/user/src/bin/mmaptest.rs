@@ -0,0 +1,59 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{mmap, munmap, run_in_child, PROT_READ, PROT_WRITE};
+
+const PAGE_SIZE: usize = 4096;
+const START: usize = 0x1000_0000;
+const PAGES: usize = 4;
+
+fn page(i: usize) -> *mut u8 {
+    (START + i * PAGE_SIZE) as *mut u8
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let len = PAGES * PAGE_SIZE;
+    assert_eq!(mmap(START, len, PROT_READ | PROT_WRITE), START as isize);
+    for i in 0..PAGES {
+        unsafe {
+            page(i).write_volatile(i as u8);
+        }
+    }
+    for i in 0..PAGES {
+        assert_eq!(unsafe { page(i).read_volatile() }, i as u8);
+    }
+    // 不合法的参数
+    assert_eq!(mmap(START + 1, PAGE_SIZE, PROT_READ), -1);
+    assert_eq!(mmap(START + len, PAGE_SIZE, 0), -1);
+    assert_eq!(mmap(START + len, PAGE_SIZE, 1 << 3), -1);
+    assert_eq!(mmap(START + len, 0, PROT_READ), -1);
+    // 和已有的映射重叠
+    assert_eq!(mmap(START + len - PAGE_SIZE, 2 * PAGE_SIZE, PROT_READ), -1);
+
+    // 解除中间一页的映射，两边的页面不受影响
+    assert_eq!(munmap(START + PAGE_SIZE, PAGE_SIZE), 0);
+    assert_eq!(unsafe { page(0).read_volatile() }, 0);
+    assert_eq!(unsafe { page(2).read_volatile() }, 2);
+    assert_eq!(run_in_child(|| unsafe {
+        page(1).read_volatile();
+    }), -2);
+    // 已经没有映射的页面不能再解除
+    assert_eq!(munmap(START, 2 * PAGE_SIZE), -1);
+    // 空出来的页面可以重新映射，内容是全0的
+    assert_eq!(mmap(START + PAGE_SIZE, PAGE_SIZE, PROT_READ), (START + PAGE_SIZE) as isize);
+    assert_eq!(unsafe { page(1).read_volatile() }, 0);
+    // 只读的页面不能写
+    assert_eq!(run_in_child(|| unsafe {
+        page(1).write_volatile(1);
+    }), -2);
+    assert_eq!(munmap(START, len), 0);
+    assert_eq!(run_in_child(|| unsafe {
+        page(0).read_volatile();
+    }), -2);
+    println!("mmaptest passed!");
+    0
+}
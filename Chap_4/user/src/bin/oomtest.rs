@@ -0,0 +1,42 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{mmap, run_in_child, PROT_READ, PROT_WRITE};
+
+const PAGE_SIZE: usize = 4096;
+/// 32MiB，超过了物理内存和交换区的总和
+const DATA_SIZE: usize = 32 << 20;
+const ENOMEM: isize = 12;
+
+static mut DATA: [u8; DATA_SIZE] = [0; DATA_SIZE];
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // mmap立即分配物理页帧，分配不到时返回-ENOMEM，已经分配的页帧都会被回收
+    assert_eq!(mmap(0x1000_0000, DATA_SIZE, PROT_READ | PROT_WRITE), -ENOMEM);
+    println!("mmap of {} MiB failed with ENOMEM", DATA_SIZE >> 20);
+    // 按需分配的页面在缺页时分配不到，子进程被OOM killer结束
+    let exit_code = run_in_child(|| unsafe {
+        let data = core::ptr::addr_of_mut!(DATA) as *mut u8;
+        for page in 0..DATA_SIZE / PAGE_SIZE {
+            data.add(page * PAGE_SIZE).write_volatile(1);
+        }
+    });
+    assert_eq!(exit_code, -9);
+    // 内存都已经回收，可以再次使用
+    let exit_code = run_in_child(|| unsafe {
+        let data = core::ptr::addr_of_mut!(DATA) as *mut u8;
+        for page in 0..16 {
+            data.add(page * PAGE_SIZE).write_volatile(page as u8);
+        }
+        for page in 0..16 {
+            assert_eq!(data.add(page * PAGE_SIZE).read_volatile(), page as u8);
+        }
+    });
+    assert_eq!(exit_code, 0);
+    println!("oomtest passed!");
+    0
+}
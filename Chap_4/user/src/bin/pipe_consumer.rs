@@ -0,0 +1,28 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::read;
+
+const STDIN: usize = 0;
+
+/// 从标准输入一直读到EOF，统计读到的字节数和行数
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut buffer = [0u8; 64];
+    let mut bytes = 0usize;
+    let mut lines = 0usize;
+    loop {
+        let len = read(STDIN, &mut buffer);
+        if len <= 0 {
+            break;
+        }
+        let len = len as usize;
+        bytes += len;
+        lines += buffer[..len].iter().filter(|&&c| c == b'\n').count();
+    }
+    println!("pipe_consumer: read {} bytes, {} lines", bytes, lines);
+    0
+}
@@ -0,0 +1,16 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+const LINES: usize = 50;
+
+/// 向标准输出写若干行，配合shell中的`pipe_producer | pipe_consumer`使用
+#[no_mangle]
+pub fn main() -> i32 {
+    for i in 0..LINES {
+        println!("line {}", i);
+    }
+    0
+}
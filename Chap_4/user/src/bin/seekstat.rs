@@ -0,0 +1,67 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, fstat, lseek, open, read, write, OpenFlags, Stat, StatMode, SEEK_CUR, SEEK_END,
+    SEEK_SET,
+};
+
+const STDOUT: usize = 1;
+const EINVAL: isize = 22;
+const EFBIG: isize = 27;
+const ESPIPE: isize = 29;
+/// easy-fs中单个文件的最大字节数：(27 + 128 + 128 * 128) * 512
+const MAX_FILE_SIZE: isize = 8467968;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fd = open("fileb", OpenFlags::CREATE | OpenFlags::TRUNC | OpenFlags::RDWR);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    assert_eq!(write(fd, b"0123456789"), 10);
+
+    // 在同一个描述符上移动读写位置
+    let mut buf = [0u8; 4];
+    assert_eq!(lseek(fd, 2, SEEK_SET), 2);
+    assert_eq!(read(fd, &mut buf), 4);
+    assert_eq!(&buf, b"2345");
+    assert_eq!(lseek(fd, -1, SEEK_CUR), 5);
+    assert_eq!(read(fd, &mut buf[..1]), 1);
+    assert_eq!(buf[0], b'5');
+    assert_eq!(lseek(fd, -3, SEEK_END), 7);
+    assert_eq!(read(fd, &mut buf), 3);
+    assert_eq!(&buf[..3], b"789");
+    // 越过文件末尾写入会扩大文件，中间的空洞读出来是0
+    assert_eq!(lseek(fd, 12, SEEK_SET), 12);
+    assert_eq!(write(fd, b"!"), 1);
+    assert_eq!(lseek(fd, 10, SEEK_SET), 10);
+    assert_eq!(read(fd, &mut buf), 3);
+    assert_eq!(&buf[..3], b"\0\0!");
+    // 非法的偏移和whence
+    assert_eq!(lseek(fd, -1, SEEK_SET), -EINVAL);
+    assert_eq!(lseek(fd, 0, 3), -EINVAL);
+    // 不能越过最大文件大小，在最大文件大小处写入失败
+    assert_eq!(lseek(fd, MAX_FILE_SIZE + 1, SEEK_SET), -EINVAL);
+    assert_eq!(lseek(fd, MAX_FILE_SIZE, SEEK_SET), MAX_FILE_SIZE);
+    assert_eq!(write(fd, b"!"), -EFBIG);
+    // 标准输出不支持定位
+    assert_eq!(lseek(STDOUT, 0, SEEK_SET), -ESPIPE);
+
+    let mut stat = Stat::new();
+    assert_eq!(fstat(fd, &mut stat), 0);
+    assert_eq!(stat.mode, StatMode::FILE);
+    assert_eq!(stat.nlink, 1);
+    assert!(stat.ino > 0);
+    // 同一个文件再打开一次，索引节点编号相同
+    let fd2 = open("fileb", OpenFlags::RDONLY) as usize;
+    let mut stat2 = Stat::new();
+    assert_eq!(fstat(fd2, &mut stat2), 0);
+    assert_eq!(stat.ino, stat2.ino);
+    close(fd2);
+    close(fd);
+    println!("seekstat passed!");
+    0
+}
@@ -0,0 +1,64 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{exit, fork, get_time, set_priority, waitpid};
+
+const PRIORITIES: [isize; 5] = [4, 6, 8, 10, 12];
+/// 所有子进程一起运行的时间，足够长才能让各自得到的时间片数接近优先级之比
+const RUN_TIME: isize = 3000;
+
+fn spin_delay() {
+    let mut x = 0usize;
+    for i in 0..100 {
+        unsafe {
+            (&mut x as *mut usize).write_volatile(i);
+        }
+    }
+}
+
+/// 一直计数到deadline，返回计数值
+fn count_until(deadline: isize) -> isize {
+    let mut count = 0;
+    loop {
+        spin_delay();
+        count += 1;
+        if count % 256 == 0 && get_time() >= deadline {
+            return count;
+        }
+    }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_eq!(set_priority(1), -1);
+    assert_eq!(set_priority(0), -1);
+    assert_eq!(set_priority(-5), -1);
+    // 父进程只在waitpid中等待，提高优先级也几乎不占用CPU
+    assert_eq!(set_priority(16), 16);
+    let deadline = get_time() + RUN_TIME;
+    let mut pids = [0isize; PRIORITIES.len()];
+    for (i, prio) in PRIORITIES.iter().enumerate() {
+        let pid = fork();
+        if pid == 0 {
+            assert_eq!(set_priority(*prio), *prio);
+            exit(count_until(deadline) as i32);
+        }
+        pids[i] = pid;
+    }
+    let mut ratios = [0isize; PRIORITIES.len()];
+    for (i, prio) in PRIORITIES.iter().enumerate() {
+        let mut count: i32 = 0;
+        assert_eq!(waitpid(pids[i] as usize, &mut count), pids[i]);
+        ratios[i] = count as isize / prio;
+        println!("priority {:>2}: count = {}, count / priority = {}", prio, count, ratios[i]);
+    }
+    // 每个进程的计数应当和它的优先级成正比
+    let max = *ratios.iter().max().unwrap();
+    let min = *ratios.iter().min().unwrap();
+    assert!(max * 2 < min * 3, "progress is not proportional to priority");
+    println!("stridetest passed!");
+    0
+}
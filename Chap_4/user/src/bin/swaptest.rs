@@ -0,0 +1,43 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+const PAGE_SIZE: usize = 4096;
+/// 6MiB，超过了内核可以分配给用户程序的物理内存，一部分页面必须换出到交换区
+const DATA_SIZE: usize = 6 << 20;
+
+static mut DATA: [u8; DATA_SIZE] = [0; DATA_SIZE];
+
+fn pattern(page: usize, round: usize) -> u8 {
+    (page * 7 + round) as u8
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let pages = DATA_SIZE / PAGE_SIZE;
+    let data = unsafe { core::ptr::addr_of_mut!(DATA) as *mut u8 };
+    for round in 0..2 {
+        for page in 0..pages {
+            unsafe {
+                let p = data.add(page * PAGE_SIZE);
+                p.write_volatile(pattern(page, round));
+                p.add(PAGE_SIZE - 1).write_volatile(pattern(page, round));
+            }
+        }
+        // 倒着检查一遍，前面写入的页面早已被换出
+        for page in (0..pages).rev() {
+            unsafe {
+                let p = data.add(page * PAGE_SIZE);
+                assert_eq!(p.read_volatile(), pattern(page, round));
+                assert_eq!(p.add(PAGE_SIZE - 1).read_volatile(), pattern(page, round));
+            }
+        }
+        // 扫描期间没有执行过的代码页面（比如println用到的格式化代码）也会被换出，
+        // 这里要经过取指时的缺页异常才能换回来
+        println!("round {} ok", round);
+    }
+    println!("swaptest passed!");
+    0
+}
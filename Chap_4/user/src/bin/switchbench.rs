@@ -0,0 +1,49 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, fork, get_time, pipe, read, wait, write};
+
+/// 父子进程通过两个管道来回传递一个字节的次数，每一个来回至少包含两次进程切换
+const ROUNDS: usize = 10000;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut ping = [0usize; 2];
+    let mut pong = [0usize; 2];
+    pipe(&mut ping);
+    pipe(&mut pong);
+    let mut byte = [0u8; 1];
+    if fork() == 0 {
+        close(ping[1]);
+        close(pong[0]);
+        for _ in 0..ROUNDS {
+            assert_eq!(read(ping[0], &mut byte), 1);
+            assert_eq!(write(pong[1], &byte), 1);
+        }
+        return 0;
+    }
+    close(ping[0]);
+    close(pong[1]);
+    let start = get_time();
+    for i in 0..ROUNDS {
+        byte[0] = i as u8;
+        assert_eq!(write(ping[1], &byte), 1);
+        assert_eq!(read(pong[0], &mut byte), 1);
+        assert_eq!(byte[0], i as u8);
+    }
+    let elapsed = get_time() - start;
+    let mut exit_code: i32 = 0;
+    wait(&mut exit_code);
+    assert_eq!(exit_code, 0);
+    println!(
+        "{} round trips in {} ms, {} us per switch",
+        ROUNDS,
+        elapsed,
+        elapsed as usize * 1000 / (ROUNDS * 2)
+    );
+    println!("switchbench passed!");
+    0
+}
@@ -0,0 +1,73 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{exit, fork, get_time, getpid, task_info, waitpid, yield_, TaskInfo};
+
+const SYSCALL_YIELD: usize = 124;
+const SYSCALL_GET_TIME: usize = 169;
+const SYSCALL_GETPID: usize = 172;
+const SYSCALL_TASK_INFO: usize = 410;
+
+const GETPID_TIMES: u32 = 100;
+const YIELD_TIMES: u32 = 10;
+
+/// 在用户态计算一段时间，确保user_time_us不为0
+fn busy_loop(ms: isize) {
+    let deadline = get_time() + ms;
+    while get_time() < deadline {}
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let pid = getpid() as usize;
+    for _ in 1..GETPID_TIMES {
+        getpid();
+    }
+    for _ in 0..YIELD_TIMES {
+        yield_();
+    }
+    busy_loop(50);
+    let mut info = TaskInfo::new();
+    assert_eq!(task_info(pid, &mut info), 0);
+    assert_eq!(info.syscall_times[SYSCALL_GETPID], GETPID_TIMES);
+    assert_eq!(info.syscall_times[SYSCALL_YIELD], YIELD_TIMES);
+    assert!(info.syscall_times[SYSCALL_GET_TIME] > 0);
+    // 正在进行的这一次也算在内
+    assert_eq!(info.syscall_times[SYSCALL_TASK_INFO], 1);
+    assert!(info.user_time_us > 0);
+    assert!(info.first_run_us > 0);
+    // 每次yield之后都要重新被调度
+    assert!(info.switches > YIELD_TIMES as usize);
+    println!(
+        "user {} us, kernel {} us, first run at {} us, {} switches",
+        info.user_time_us, info.kernel_time_us, info.first_run_us, info.switches
+    );
+
+    let child = fork();
+    if child == 0 {
+        for _ in 0..GETPID_TIMES {
+            getpid();
+        }
+        exit(0);
+    }
+    // 子进程退出之后、被回收之前仍然可以查询
+    let mut exit_code: i32 = 0;
+    let mut child_info = TaskInfo::new();
+    while task_info(child as usize, &mut child_info) == 0
+        && child_info.syscall_times[SYSCALL_GETPID] < GETPID_TIMES
+    {
+        yield_();
+    }
+    assert_eq!(child_info.syscall_times[SYSCALL_GETPID], GETPID_TIMES);
+    assert!(child_info.first_run_us >= info.first_run_us);
+    assert_eq!(waitpid(child as usize, &mut exit_code), child);
+    assert_eq!(exit_code, 0);
+    // 已经回收的子进程和不存在的进程都查询不到
+    assert_eq!(task_info(child as usize, &mut child_info), -1);
+    assert_eq!(task_info(usize::MAX, &mut child_info), -1);
+    println!("taskinfo passed!");
+    0
+}
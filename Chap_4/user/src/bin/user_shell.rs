@@ -0,0 +1,121 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+const LF: u8 = 0x0au8;
+const CR: u8 = 0x0du8;
+const DL: u8 = 0x7fu8;
+const BS: u8 = 0x08u8;
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use user_lib::console::getchar;
+use user_lib::{close, dup, exec, fork, pipe, waitpid};
+
+const STDIN: usize = 0;
+const STDOUT: usize = 1;
+
+/// 把命令行按'|'切分，每段去掉首尾空格后以'\0'结尾，有空的段时返回None
+fn split_pipeline(line: &str) -> Option<Vec<String>> {
+    line.split('|')
+        .map(|seg| {
+            let name = seg.trim_matches(' ');
+            if name.is_empty() {
+                return None;
+            }
+            let mut cmd = String::from(name);
+            cmd.push('\0');
+            Some(cmd)
+        })
+        .collect()
+}
+
+/// 依次fork出流水线中的每个应用，相邻两个应用之间用管道连接
+fn run_pipeline(cmds: &[String]) {
+    let mut pids = Vec::new();
+    // 上一个应用输出端管道的读端
+    let mut prev_read: Option<usize> = None;
+    for (i, cmd) in cmds.iter().enumerate() {
+        let mut pipe_fd = [0usize; 2];
+        let has_next = i + 1 < cmds.len();
+        if has_next {
+            pipe(&mut pipe_fd);
+        }
+        let pid = fork();
+        if pid == 0 {
+            // child process: 用dup把管道端口换到标准输入输出的位置上
+            if let Some(read_end) = prev_read {
+                close(STDIN);
+                assert_eq!(dup(read_end), STDIN as isize);
+                close(read_end);
+            }
+            if has_next {
+                close(pipe_fd[0]);
+                close(STDOUT);
+                assert_eq!(dup(pipe_fd[1]), STDOUT as isize);
+                close(pipe_fd[1]);
+            }
+            if exec(cmd) < 0 {
+                println!("Error when executing {}!", cmd.trim_end_matches('\0'));
+                user_lib::exit(-4);
+            }
+            unreachable!();
+        }
+        pids.push(pid);
+        // 父进程必须关闭自己持有的管道端口，否则读端永远等不到EOF
+        if let Some(read_end) = prev_read {
+            close(read_end);
+        }
+        if has_next {
+            close(pipe_fd[1]);
+            prev_read = Some(pipe_fd[0]);
+        }
+    }
+    for pid in pids {
+        let mut exit_code: i32 = 0;
+        let exit_pid = waitpid(pid as usize, &mut exit_code);
+        assert_eq!(pid, exit_pid);
+        println!("Shell: Process {} exited with code {}", pid, exit_code);
+    }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    println!("Rust user shell");
+    let mut line = String::new();
+    print!(">> ");
+    loop {
+        let c = getchar();
+        match c {
+            LF | CR => {
+                println!("");
+                if !line.is_empty() {
+                    match split_pipeline(&line) {
+                        Some(cmds) => run_pipeline(&cmds),
+                        None => println!("Invalid pipeline!"),
+                    }
+                    line.clear();
+                }
+                print!(">> ");
+            }
+            BS | DL => {
+                if !line.is_empty() {
+                    // 退格：光标左移，用空格覆盖掉原字符，再把光标移回来
+                    print!("{}", BS as char);
+                    print!(" ");
+                    print!("{}", BS as char);
+                    line.pop();
+                }
+            }
+            _ => {
+                if c.is_ascii_graphic() || c == b' ' {
+                    print!("{}", c as char);
+                    line.push(c as char);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,71 @@
+//! 文件相关的常量和数据结构，与内核中的定义保持一致
+use bitflags::*;
+
+/// `openat`的`dirfd`取该值时表示相对于当前工作目录
+pub const AT_FDCWD: isize = -100;
+
+/// `unlinkat`的`flags`中带有该位时删除的是目录
+pub const AT_REMOVEDIR: u32 = 0x200;
+
+/// 从文件开头计算偏移
+pub const SEEK_SET: usize = 0;
+/// 从当前位置计算偏移
+pub const SEEK_CUR: usize = 1;
+/// 从文件末尾计算偏移
+pub const SEEK_END: usize = 2;
+
+bitflags! {
+    /// 打开文件时的选项，取值和Linux一致
+    pub struct OpenFlags: u32 {
+        const RDONLY = 0;
+        const WRONLY = 1 << 0;
+        const RDWR = 1 << 1;
+        const CREATE = 0o100;
+        const TRUNC = 0o1000;
+        const APPEND = 0o2000;
+    }
+}
+
+/// 文件的元数据
+#[repr(C)]
+#[derive(Debug)]
+pub struct Stat {
+    /// 文件所在的设备号
+    pub dev: u64,
+    /// 索引节点编号
+    pub ino: u64,
+    /// 文件类型
+    pub mode: StatMode,
+    /// 硬链接数
+    pub nlink: u32,
+    pad: [u64; 7],
+}
+
+impl Stat {
+    pub fn new() -> Self {
+        Self {
+            dev: 0,
+            ino: 0,
+            mode: StatMode::NULL,
+            nlink: 0,
+            pad: [0; 7],
+        }
+    }
+}
+
+impl Default for Stat {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+bitflags! {
+    /// 文件类型
+    pub struct StatMode: u32 {
+        const NULL = 0;
+        /// 目录
+        const DIR = 0o040000;
+        /// 普通文件
+        const FILE = 0o100000;
+    }
+}
@@ -0,0 +1,44 @@
+//! 用户程序的堆：堆中空间不够时通过sbrk向内核申请，再交给伙伴系统分配器管理
+use crate::sbrk;
+use buddy_system_allocator::LockedHeap;
+use core::alloc::{GlobalAlloc, Layout};
+use core::ptr::{null_mut, NonNull};
+
+const PAGE_SIZE: usize = 0x1000;
+/// 每次至少向内核申请的大小
+const HEAP_GROW_SIZE: usize = 4 * PAGE_SIZE;
+
+struct SbrkHeap(LockedHeap);
+
+#[global_allocator]
+static HEAP: SbrkHeap = SbrkHeap(LockedHeap::empty());
+
+unsafe impl GlobalAlloc for SbrkHeap {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let mut heap = self.0.lock();
+        if let Ok(ptr) = heap.alloc(layout) {
+            return ptr.as_ptr();
+        }
+        // 伙伴系统只能分配按大小对齐的块，新申请的空间要比所需的块大一倍才能保证放得下，
+        // 堆的起始地址和每次申请的大小都是页对齐的，所以不超过一页的块总能放下
+        let block = layout.size().max(layout.align()).next_power_of_two();
+        let size = (2 * block).max(HEAP_GROW_SIZE);
+        if size > i32::MAX as usize {
+            return null_mut();
+        }
+        let start = sbrk(size as i32);
+        if start < 0 {
+            return null_mut();
+        }
+        heap.add_to_heap(start as usize, start as usize + size);
+        heap.alloc(layout).map_or(null_mut(), |ptr| ptr.as_ptr())
+    }
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.0.lock().dealloc(NonNull::new_unchecked(ptr), layout);
+    }
+}
+
+#[alloc_error_handler]
+pub fn handle_alloc_error(layout: Layout) -> ! {
+    panic!("Heap allocation error, layout = {:?}", layout);
+}
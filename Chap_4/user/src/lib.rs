@@ -1,12 +1,17 @@
 #![no_std]
 #![feature(linkage)]
 #![feature(panic_info_message)]
+#![feature(alloc_error_handler)]
 
 #[macro_use]
 pub mod console;
+mod fs;
+mod heap;
 mod lang_items;
 mod syscall;
 
+extern crate alloc;
+
 #[no_mangle]
 #[link_section = ".text.entry"]
 pub extern "C" fn _start() -> ! {
@@ -31,17 +36,222 @@ fn clear_bss() {
     });
 }
 
+pub use fs::*;
 use syscall::*;
 
+pub fn dup(fd: usize) -> isize {
+    sys_dup(fd)
+}
+/// 在当前工作目录下打开文件，返回文件描述符
+pub fn open(path: &str, flags: OpenFlags) -> isize {
+    with_c_path(path, |path| sys_openat(AT_FDCWD, path, flags.bits()))
+}
+/// 创建目录
+pub fn mkdir(path: &str) -> isize {
+    with_c_path(path, |path| sys_mkdirat(AT_FDCWD, path))
+}
+/// 为`old_path`指向的文件创建硬链接`new_path`
+pub fn link(old_path: &str, new_path: &str) -> isize {
+    with_c_path(old_path, |old_path| {
+        with_c_path(new_path, |new_path| {
+            sys_linkat(AT_FDCWD, old_path, AT_FDCWD, new_path, 0)
+        })
+    })
+}
+/// 删除文件的一个链接
+pub fn unlink(path: &str) -> isize {
+    with_c_path(path, |path| sys_unlinkat(AT_FDCWD, path, 0))
+}
+/// 删除空目录
+pub fn rmdir(path: &str) -> isize {
+    with_c_path(path, |path| sys_unlinkat(AT_FDCWD, path, AT_REMOVEDIR))
+}
+pub fn chdir(path: &str) -> isize {
+    with_c_path(path, sys_chdir)
+}
+/// 把当前工作目录写入`buf`，返回路径的长度（不含结尾的'\0'）
+pub fn getcwd(buf: &mut [u8]) -> isize {
+    match sys_getcwd(buf) {
+        len if len > 0 => len - 1,
+        err => err,
+    }
+}
+pub fn close(fd: usize) -> isize {
+    sys_close(fd)
+}
+/// 创建管道，`pipe_fd[0]`为读端，`pipe_fd[1]`为写端
+pub fn pipe(pipe_fd: &mut [usize]) -> isize {
+    sys_pipe(pipe_fd)
+}
+pub fn read(fd: usize, buf: &mut [u8]) -> isize {
+    sys_read(fd, buf)
+}
 pub fn write(fd: usize, buf: &[u8]) -> isize {
     sys_write(fd, buf)
 }
+/// 移动读写位置，`whence`取SEEK_SET/SEEK_CUR/SEEK_END，返回新的位置
+pub fn lseek(fd: usize, offset: isize, whence: usize) -> isize {
+    sys_lseek(fd, offset, whence)
+}
+pub fn fstat(fd: usize, st: &mut Stat) -> isize {
+    sys_fstat(fd, st)
+}
 pub fn exit(exit_code: i32) -> isize {
     sys_exit(exit_code)
 }
 pub fn yield_() -> isize {
     sys_yield()
 }
+/// nanosleep的参数，布局与内核中的TimeSpec一致
+#[repr(C)]
+pub struct TimeSpec {
+    pub tv_sec: usize,
+    pub tv_nsec: usize,
+}
+
+/// 睡眠`req`指定的时长，期间不占用CPU
+pub fn nanosleep(req: &TimeSpec) -> isize {
+    sys_nanosleep(req)
+}
+/// 睡眠ms毫秒，期间不占用CPU
+pub fn sleep(ms: usize) -> isize {
+    nanosleep(&TimeSpec {
+        tv_sec: ms / 1000,
+        tv_nsec: ms % 1000 * 1_000_000,
+    })
+}
+/// 设置调度优先级，优先级越大得到的CPU时间越多，不能小于2
+pub fn set_priority(prio: isize) -> isize {
+    sys_set_priority(prio)
+}
 pub fn get_time() -> isize {
     sys_get_time()
 }
+pub fn getpid() -> isize {
+    sys_getpid()
+}
+
+/// 系统调用编号的上限，与内核一致
+pub const MAX_SYSCALL_NUM: usize = 500;
+
+/// 进程的运行统计，布局与内核中的TaskInfo一致
+#[repr(C)]
+pub struct TaskInfo {
+    pub user_time_us: usize,
+    pub kernel_time_us: usize,
+    /// 第一次被调度执行的时刻（开机以来的微秒数）
+    pub first_run_us: usize,
+    /// 被调度执行的次数
+    pub switches: usize,
+    /// 每个系统调用被调用的次数，下标为系统调用编号
+    pub syscall_times: [u32; MAX_SYSCALL_NUM],
+}
+
+impl TaskInfo {
+    pub fn new() -> Self {
+        Self {
+            user_time_us: 0,
+            kernel_time_us: 0,
+            first_run_us: 0,
+            switches: 0,
+            syscall_times: [0; MAX_SYSCALL_NUM],
+        }
+    }
+}
+
+impl Default for TaskInfo {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 查询当前进程或者它的子进程的运行统计，其他pid返回-1
+pub fn task_info(pid: usize, info: &mut TaskInfo) -> isize {
+    sys_task_info(pid, info)
+}
+/// 把program break移动size字节，返回原来的program break，失败时返回-1
+pub fn sbrk(size: i32) -> isize {
+    sys_sbrk(size)
+}
+pub const PROT_READ: usize = 1 << 0;
+pub const PROT_WRITE: usize = 1 << 1;
+pub const PROT_EXEC: usize = 1 << 2;
+
+/// 写入的内容会写回文件
+pub const MAP_SHARED: usize = 0x01;
+/// 写入的内容只有自己可见
+pub const MAP_PRIVATE: usize = 0x02;
+pub const MAP_ANONYMOUS: usize = 0x20;
+
+/// 在[start, start + len)映射一段匿名内存，成功时返回start，失败时返回-1
+pub fn mmap(start: usize, len: usize, prot: usize) -> isize {
+    sys_mmap(start, len, prot, MAP_PRIVATE | MAP_ANONYMOUS, 0, 0)
+}
+/// 把fd对应文件从offset开始的内容映射到[start, start + len)，flags为MAP_SHARED或MAP_PRIVATE
+pub fn mmap_file(start: usize, len: usize, prot: usize, flags: usize, fd: usize, offset: usize) -> isize {
+    sys_mmap(start, len, prot, flags, fd, offset)
+}
+/// 把[start, start + len)中MAP_SHARED文件映射被写过的页面写回文件
+pub fn msync(start: usize, len: usize) -> isize {
+    sys_msync(start, len)
+}
+pub fn munmap(start: usize, len: usize) -> isize {
+    sys_munmap(start, len)
+}
+pub fn fork() -> isize {
+    sys_fork()
+}
+const MAX_PATH_LEN: usize = 128;
+
+/// 内核按'\0'结尾的字符串读取路径，`path`没有以'\0'结尾时在栈上拷贝一份并补上，
+/// 因此`exec("01power_5")`和`exec("01power_5\0")`都可以
+fn with_c_path(path: &str, f: impl FnOnce(&str) -> isize) -> isize {
+    if path.ends_with('\0') {
+        return f(path);
+    }
+    let bytes = path.as_bytes();
+    if bytes.len() >= MAX_PATH_LEN {
+        return -1;
+    }
+    let mut buf = [0u8; MAX_PATH_LEN];
+    buf[..bytes.len()].copy_from_slice(bytes);
+    f(unsafe { core::str::from_utf8_unchecked(&buf[..=bytes.len()]) })
+}
+
+pub fn exec(path: &str) -> isize {
+    with_c_path(path, sys_exec)
+}
+/// 等待任意一个子进程退出，子进程都还在运行时主动让出CPU
+pub fn wait(exit_code: &mut i32) -> isize {
+    loop {
+        match sys_waitpid(-1, exit_code as *mut _) {
+            -2 => {
+                yield_();
+            }
+            // -1 or a real pid
+            exit_pid => return exit_pid,
+        }
+    }
+}
+pub fn waitpid(pid: usize, exit_code: &mut i32) -> isize {
+    loop {
+        match sys_waitpid(pid as isize, exit_code as *mut _) {
+            -2 => {
+                yield_();
+            }
+            // -1 or a real pid
+            exit_pid => return exit_pid,
+        }
+    }
+}
+/// 在子进程中执行`f`，返回子进程的退出码，用来检查会被内核杀死的访存
+pub fn run_in_child(f: fn()) -> i32 {
+    let pid = fork();
+    if pid == 0 {
+        f();
+        exit(0);
+    }
+    let mut exit_code: i32 = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    exit_code
+}